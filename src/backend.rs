@@ -0,0 +1,467 @@
+use crate::{
+    chapters::{Chapter, ChapterList},
+    encoder::EncoderSettings,
+    helper::{concat_list, ffmpeg_timestamp, run_ffmpeg, run_ffmpeg_analysis},
+    loudness::{LoudnessMeasurement, LoudnessTarget},
+    processing::SilenceSettings,
+    progress::Event,
+    Error, Result,
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioInfo {
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub channel_layout: String,
+    pub duration: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+    pub path: PathBuf,
+    pub audio: Option<AudioInfo>,
+    pub tags: BTreeMap<String, String>,
+    pub chapters: Vec<Chapter>,
+}
+
+impl MediaInfo {
+    pub fn audio(&self) -> Result<&AudioInfo> {
+        self.audio.as_ref().ok_or_else(|| Error::ProbeError {
+            path: self.path.clone(),
+        })
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobInput {
+    pub path: PathBuf,
+    pub filter: Option<String>,
+}
+
+impl JobInput {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            filter: None,
+        }
+    }
+
+    pub fn filtered(path: impl Into<PathBuf>, filter: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            filter: Some(filter.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscodeJob {
+    pub inputs: Vec<JobInput>,
+    pub range: Option<(u32, u32)>,
+    pub encoder: EncoderSettings,
+    pub chapters: Option<ChapterList>,
+    pub metadata: Vec<(String, String)>,
+    pub all_streams: bool,
+    pub output: PathBuf,
+    pub duration: Option<u32>,
+}
+
+impl TranscodeJob {
+    pub fn new(
+        inputs: Vec<JobInput>,
+        encoder: EncoderSettings,
+        output: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            inputs,
+            range: None,
+            encoder,
+            chapters: None,
+            metadata: Vec::new(),
+            all_streams: false,
+            output: output.into(),
+            duration: None,
+        }
+    }
+}
+
+/// The media operations the commands need. There is no separate remux
+/// operation: a `TranscodeJob` whose encoder is `Codec::Copy` remuxes.
+pub trait MediaBackend: Send + Sync {
+    fn probe(&self, path: &Path) -> Result<MediaInfo>;
+
+    /// Length of the audio in milliseconds.
+    fn duration(&self, path: &Path) -> Result<u32> {
+        Ok(self.probe(path)?.audio()?.duration)
+    }
+
+    fn transcode(&self, job: &TranscodeJob) -> Result<()>;
+
+    fn detect_silence(
+        &self,
+        path: &Path,
+        settings: &SilenceSettings,
+    ) -> Result<Vec<(u32, Option<u32>)>>;
+
+    fn measure_loudness(&self, path: &Path, target: &LoudnessTarget)
+        -> Result<LoudnessMeasurement>;
+
+    fn report(&self, _event: Event) {}
+}
+
+pub struct FfmpegBackend {
+    ffmpeg_path: String,
+    ffprobe_path: String,
+    quiet: bool,
+    overwrite: bool,
+    reporter: Option<Box<dyn Fn(Event) + Send + Sync>>,
+}
+
+impl FfmpegBackend {
+    pub fn new(ffmpeg_path: impl Into<String>) -> Self {
+        Self {
+            ffmpeg_path: ffmpeg_path.into(),
+            ffprobe_path: "ffprobe".to_string(),
+            quiet: false,
+            overwrite: false,
+            reporter: None,
+        }
+    }
+
+    pub fn ffmpeg_path(&self) -> &str {
+        &self.ffmpeg_path
+    }
+
+    pub fn ffprobe_path(&self) -> &str {
+        &self.ffprobe_path
+    }
+
+    pub fn set_ffprobe_path(&mut self, ffprobe_path: impl Into<String>) {
+        self.ffprobe_path = ffprobe_path.into();
+    }
+
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn set_overwrite(&mut self, overwrite: bool) {
+        self.overwrite = overwrite;
+    }
+
+    pub fn set_reporter(&mut self, reporter: impl Fn(Event) + Send + Sync + 'static) {
+        self.reporter = Some(Box::new(reporter));
+    }
+
+    fn analyze_with_filter(&self, path: &Path, filter: &str) -> Result<String> {
+        let input = path.to_string_lossy();
+        let arguments = [
+            "-hide_banner",
+            "-nostats",
+            "-i",
+            &input,
+            "-af",
+            filter,
+            "-f",
+            "null",
+            "-",
+        ];
+        run_ffmpeg_analysis(&self.ffmpeg_path, arguments, path)
+    }
+}
+
+impl fmt::Debug for FfmpegBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfmpegBackend")
+            .field("ffmpeg_path", &self.ffmpeg_path)
+            .field("ffprobe_path", &self.ffprobe_path)
+            .field("quiet", &self.quiet)
+            .field("overwrite", &self.overwrite)
+            .field("reporter", &self.reporter.is_some())
+            .finish()
+    }
+}
+
+impl Default for FfmpegBackend {
+    fn default() -> Self {
+        FfmpegBackend::new("ffmpeg")
+    }
+}
+
+impl MediaBackend for FfmpegBackend {
+    fn probe(&self, path: &Path) -> Result<MediaInfo> {
+        let output = match Command::new(&self.ffprobe_path)
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-select_streams",
+                "a:0",
+                "-show_streams",
+                "-show_format",
+                "-show_chapters",
+            ])
+            .arg(path)
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::FfprobeNotFoundError());
+            }
+            Err(err) => return Err(Error::IoError(err)),
+        };
+        let probe_json: Value =
+            serde_json::from_slice(&output.stdout).map_err(|source| Error::ProbeOutputError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        let tags: BTreeMap<String, String> = probe_json["format"]["tags"]
+            .as_object()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|(key, value)| {
+                        Some((key.to_lowercase(), value.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut chapters: Vec<Chapter> = Vec::new();
+        for (index, json_chapter) in probe_json["chapters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let chapter_error = |field: &'static str| Error::ChapterReadError {
+                path: path.to_path_buf(),
+                index,
+                field,
+            };
+            let title: &str = json_chapter["tags"]["title"]
+                .as_str()
+                .ok_or_else(|| chapter_error("title"))?;
+            let milliseconds = |field: &'static str| {
+                json_chapter[field]
+                    .as_str()
+                    .and_then(|seconds| seconds.parse::<f64>().ok())
+                    .map(|seconds| (seconds * 1000.0).round() as u32)
+                    .ok_or_else(|| chapter_error(field))
+            };
+            let start: u32 = milliseconds("start_time")?;
+            let end: u32 = milliseconds("end_time")?;
+            chapters.push(Chapter::new(title, start, end));
+        }
+
+        Ok(MediaInfo {
+            path: path.to_path_buf(),
+            audio: audio_info(&probe_json),
+            tags,
+            chapters,
+        })
+    }
+
+    // Reading the mp3 frame headers is much faster than starting ffprobe.
+    fn duration(&self, path: &Path) -> Result<u32> {
+        let is_mp3 = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"));
+        if !is_mp3 {
+            return Ok(self.probe(path)?.audio()?.duration);
+        }
+        match mp3_duration::from_path(path) {
+            Ok(duration) => Ok(duration.as_millis() as u32),
+            Err(source) => Err(Error::NoDurationError {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    fn transcode(&self, job: &TranscodeJob) -> Result<()> {
+        let mut arguments: Vec<String> = Vec::new();
+        if let Some((start, end)) = job.range {
+            arguments.extend([
+                "-ss".to_string(),
+                ffmpeg_timestamp(start),
+                "-to".to_string(),
+                ffmpeg_timestamp(end),
+            ]);
+        }
+
+        let filtered = job.inputs.iter().any(|input| input.filter.is_some());
+        let mut concat_tmp = NamedTempFile::new()?;
+        let input_count = if job.inputs.len() > 1 && !filtered {
+            concat_tmp
+                .write_all(concat_list(job.inputs.iter().map(|input| &input.path)).as_bytes())?;
+            arguments.extend(["-f", "concat", "-safe", "0", "-i"].map(str::to_string));
+            arguments.push(concat_tmp.path().to_string_lossy().into_owned());
+            1
+        } else {
+            for input in &job.inputs {
+                arguments.push("-i".to_string());
+                arguments.push(input.path.to_string_lossy().into_owned());
+            }
+            job.inputs.len()
+        };
+
+        let mut ffmetadata_tmp = NamedTempFile::new()?;
+        if let Some(chapters) = &job.chapters {
+            ffmetadata_tmp.write_all(chapters.ffmetadata().as_bytes())?;
+            arguments.push("-i".to_string());
+            arguments.push(ffmetadata_tmp.path().to_string_lossy().into_owned());
+        }
+
+        if filtered && job.inputs.len() > 1 {
+            let mut filter_graph = String::new();
+            for (i, input) in job.inputs.iter().enumerate() {
+                let filter = input.filter.as_deref().unwrap_or("anull");
+                filter_graph.push_str(&format!("[{i}:a]{filter}[a{i}];"));
+            }
+            for i in 0..job.inputs.len() {
+                filter_graph.push_str(&format!("[a{i}]"));
+            }
+            filter_graph.push_str(&format!("concat=n={}:v=0:a=1[out]", job.inputs.len()));
+            arguments.extend([
+                "-filter_complex".to_string(),
+                filter_graph,
+                "-map".to_string(),
+                "[out]".to_string(),
+            ]);
+        } else if !job.all_streams {
+            arguments.extend(["-map", "0:a"].map(str::to_string));
+        }
+        if let [JobInput {
+            filter: Some(filter),
+            ..
+        }] = job.inputs.as_slice()
+        {
+            arguments.extend(["-af".to_string(), filter.clone()]);
+        }
+
+        let metadata_input = match job.chapters {
+            Some(_) => input_count.to_string(),
+            None => "-1".to_string(),
+        };
+        arguments.extend([
+            "-map_metadata".to_string(),
+            metadata_input.clone(),
+            "-map_chapters".to_string(),
+            metadata_input,
+        ]);
+        for (key, value) in &job.metadata {
+            arguments.extend(["-metadata".to_string(), format!("{key}={value}")]);
+        }
+
+        if job.all_streams {
+            arguments.extend(["-c", "copy"].map(str::to_string));
+        }
+        arguments.extend(job.encoder.ffmpeg_arguments()?);
+        arguments.push(job.output.to_string_lossy().into_owned());
+
+        let inputs: Vec<&Path> = job
+            .inputs
+            .iter()
+            .map(|input| input.path.as_path())
+            .collect();
+        run_ffmpeg(
+            &self.ffmpeg_path,
+            arguments.iter().map(String::as_str),
+            &inputs,
+            job.duration,
+            &|event| self.report(event),
+            self.overwrite,
+        )?;
+        if job.duration.is_some() {
+            self.report(Event::Status("Finished".to_string()));
+        }
+        Ok(())
+    }
+
+    fn detect_silence(
+        &self,
+        path: &Path,
+        settings: &SilenceSettings,
+    ) -> Result<Vec<(u32, Option<u32>)>> {
+        let output = self.analyze_with_filter(path, &settings.detection_filter())?;
+        let mut silences: Vec<(u32, Option<u32>)> = Vec::new();
+        for line in output.lines() {
+            if let Some(start) = silence_value(line, "silence_start:") {
+                silences.push((start, None));
+            } else if let Some(end) = silence_value(line, "silence_end:") {
+                if let Some(last) = silences.last_mut() {
+                    last.1 = Some(end);
+                }
+            }
+        }
+        Ok(silences)
+    }
+
+    fn measure_loudness(
+        &self,
+        path: &Path,
+        target: &LoudnessTarget,
+    ) -> Result<LoudnessMeasurement> {
+        let output = self.analyze_with_filter(path, &target.analysis_filter())?;
+        LoudnessMeasurement::from_loudnorm_output(&output).ok_or_else(|| {
+            Error::LoudnessAnalysisError {
+                path: path.to_path_buf(),
+            }
+        })
+    }
+
+    fn report(&self, event: Event) {
+        if let Some(reporter) = self.reporter.as_ref().filter(|_| !self.quiet) {
+            reporter(event);
+        }
+    }
+}
+
+fn silence_value(line: &str, key: &str) -> Option<u32> {
+    let value = line.split(key).nth(1)?.split_whitespace().next()?;
+    let seconds: f64 = value.parse().ok()?;
+    Some((seconds.max(0.0) * 1000.0).round() as u32)
+}
+
+fn audio_info(probe_json: &Value) -> Option<AudioInfo> {
+    let stream = &probe_json["streams"][0];
+    let seconds: f64 = probe_json["format"]["duration"]
+        .as_str()
+        .and_then(|duration| duration.parse().ok())?;
+
+    Some(AudioInfo {
+        codec: stream["codec_name"].as_str()?.to_string(),
+        sample_rate: stream["sample_rate"]
+            .as_str()
+            .and_then(|sample_rate| sample_rate.parse().ok())?,
+        channels: stream["channels"].as_u64()? as u32,
+        channel_layout: stream["channel_layout"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        duration: (seconds * 1000.0).round() as u32,
+    })
+}
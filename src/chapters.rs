@@ -1,20 +1,21 @@
-use crate::{read_tag, run_ffmpeg, Error, Result};
-use core::str;
+use crate::{
+    backend::{JobInput, MediaBackend, TranscodeJob},
+    encoder::{Codec, EncoderSettings},
+    metadata::{escape_ffmetadata, BookMetadata},
+    processing::InputFile,
+    read_tag, Error, Result,
+};
 use id3::TagLike;
 use prettytable::{row, Table};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::{
     fmt::Display,
-    io::{self, Write},
     ops::{Index, IndexMut},
-    path::{Path, PathBuf},
-    process::Command,
+    path::Path,
     slice::{Iter, IterMut},
 };
-use tempfile::NamedTempFile;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Chapter {
     title: String,
     start: u32,
@@ -63,15 +64,49 @@ impl Chapter {
             END={}\n\
             title={}\n\
 ",
-            self.start, self.end, self.title
+            self.start,
+            self.end,
+            escape_ffmetadata(&self.title)
         )
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub trait ChapterSource {
+    fn path(&self) -> &Path;
+
+    fn duration(&self, backend: &dyn MediaBackend) -> Result<u32>;
+}
+
+impl<T: AsRef<Path>> ChapterSource for T {
+    fn path(&self) -> &Path {
+        self.as_ref()
+    }
+
+    fn duration(&self, backend: &dyn MediaBackend) -> Result<u32> {
+        backend.duration(self.as_ref())
+    }
+}
+
+impl ChapterSource for &InputFile {
+    fn path(&self) -> &Path {
+        InputFile::path(self)
+    }
+
+    fn duration(&self, _backend: &dyn MediaBackend) -> Result<u32> {
+        Ok(InputFile::duration(self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChapterList {
     title: String,
     author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    narrator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    genre: Option<String>,
+    #[serde(default, skip_serializing_if = "BookMetadata::is_empty")]
+    metadata: BookMetadata,
     chapters: Vec<Chapter>,
 }
 
@@ -80,22 +115,26 @@ impl ChapterList {
         Self {
             title: title.into(),
             author: author.into(),
+            narrator: None,
+            genre: None,
+            metadata: BookMetadata::default(),
             chapters: Vec::new(),
         }
     }
 
     pub fn from_path_set(
-        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+        paths: impl IntoIterator<Item = impl ChapterSource>,
         title: impl Into<String>,
         author: impl Into<String>,
+        backend: &dyn MediaBackend,
     ) -> Result<Self> {
         let mut chapters: Vec<Chapter> = Vec::new();
         let mut playhead: u32 = 0;
 
-        for (i, path) in paths.into_iter().enumerate() {
-            let tag = read_tag(&path)?;
+        for (i, source) in paths.into_iter().enumerate() {
+            let tag = read_tag(source.path())?;
             let chapter_title = tag.title().unwrap_or(&i.to_string()).to_string();
-            let duration = mp3_duration::from_path(path)?.as_millis() as u32;
+            let duration = source.duration(backend)?;
             let start = playhead;
             let end = playhead + duration;
 
@@ -106,52 +145,33 @@ impl ChapterList {
         Ok(Self {
             title: title.into(),
             author: author.into(),
+            narrator: None,
+            genre: None,
+            metadata: BookMetadata::default(),
             chapters,
         })
     }
 
-    pub fn from_chaptered_file(path: &str) -> Result<ChapterList> {
-        let path = path.as_ref();
-        let arguments = [
-            "-v",
-            "quiet",
-            "-print_format",
-            "json",
-            "-show_chapters",
-            path,
-        ];
-        let output = match Command::new("ffprobe").args(arguments).output() {
-            Ok(output) => output,
-            Err(err) if err.kind() == io::ErrorKind::NotFound => {
-                return Err(Error::FfprobeNotFoundError());
-            }
-            Err(err) => return Err(Error::IoError(err)),
-        };
-        let tag = read_tag(&PathBuf::from(path))?;
-        let title = tag.title().unwrap_or("Unknown title").to_string();
-        let author = tag.artist().unwrap_or("Unknown author").to_string();
+    pub fn from_chaptered_file(
+        path: impl AsRef<Path>,
+        backend: &dyn MediaBackend,
+    ) -> Result<ChapterList> {
+        let media_info = backend.probe(path.as_ref())?;
+        let tag = read_tag(path)?;
+        let title = media_info
+            .tag("title")
+            .or(tag.title())
+            .unwrap_or("Unknown title");
+        let author = media_info
+            .tag("artist")
+            .or(tag.artist())
+            .unwrap_or("Unknown author");
         let mut chapter_list = ChapterList::new(title, author);
-
-        let chapters_json: Value = serde_json::from_str(
-            str::from_utf8(&output.stdout).map_err(|_| Error::ChapterReadError)?,
-        )
-        .unwrap();
-        for json_chapter in chapters_json["chapters"]
-            .as_array()
-            .ok_or(Error::ChapterReadError)?
-        {
-            let title: &str = json_chapter["tags"]["title"]
-                .as_str()
-                .ok_or(Error::ChapterReadError)?;
-            let start: u32 = json_chapter["start"]
-                .as_i64()
-                .ok_or(Error::ChapterReadError)? as u32;
-            let end: u32 = json_chapter["end"]
-                .as_i64()
-                .ok_or(Error::ChapterReadError)? as u32;
-
-            chapter_list.push(Chapter::new(title, start, end));
-        }
+        chapter_list.narrator = media_info.tag("composer").map(str::to_string);
+        chapter_list.genre = media_info.tag("genre").map(str::to_string);
+        chapter_list.metadata =
+            BookMetadata::from_media_info(&media_info).or(BookMetadata::from_tag(&tag));
+        chapter_list.chapters = media_info.chapters;
         Ok(chapter_list)
     }
 
@@ -160,10 +180,16 @@ impl ChapterList {
             ";FFMETADATA\n\
             title={}\n\
             artist={}\n\
-            genre=AudioBook\n\
+            genre={}\n\
 ",
-            self.title, self.author
+            escape_ffmetadata(&self.title),
+            escape_ffmetadata(&self.author),
+            escape_ffmetadata(self.genre.as_deref().unwrap_or("AudioBook"))
         );
+        if let Some(narrator) = &self.narrator {
+            ffmetadata.push_str(&format!("composer={}\n", escape_ffmetadata(narrator)));
+        }
+        ffmetadata.push_str(&self.metadata.ffmetadata());
         for chapter in &self.chapters {
             ffmetadata.push_str(&chapter.ffmetadata());
         }
@@ -177,38 +203,69 @@ impl ChapterList {
 
     pub fn write_to_file(
         &self,
-        input_path: &str,
-        output_path: &str,
-        ffmpeg_path: &str,
+        input_path: impl AsRef<Path>,
+        output_path: impl AsRef<Path>,
+        backend: &dyn MediaBackend,
     ) -> Result<()> {
-        let ffmetadata: String = self.ffmetadata();
-
-        let mut ffmetadata_tmp = NamedTempFile::new()?;
-        ffmetadata_tmp.write_all(ffmetadata.as_bytes())?;
-        let ffmetadata_tmp_path = ffmetadata_tmp.path().to_string_lossy();
-
-        let arguments = [
-            "-i",
-            input_path,
-            "-i",
-            &ffmetadata_tmp_path,
-            // "-map",
-            // "0",
-            "-map_metadata",
-            "1",
-            "-map_chapters",
-            "1",
-            "-c",
-            "copy",
-            output_path,
-        ];
-        run_ffmpeg(ffmpeg_path, arguments)?;
+        let mut job = TranscodeJob::new(
+            vec![JobInput::new(input_path.as_ref())],
+            EncoderSettings::new(Codec::Copy),
+            output_path.as_ref(),
+        );
+        job.chapters = Some(self.clone());
+        job.all_streams = true;
+        job.duration = Some(self.end());
+        backend.transcode(&job)?;
 
         Ok(())
     }
 
+    pub fn split_into_parts(&self, max_duration: u32) -> Vec<ChapterList> {
+        let mut parts: Vec<ChapterList> = Vec::new();
+        let mut current = self.with_chapters(Vec::new());
+
+        for chapter in &self.chapters {
+            if !current.is_empty() && chapter.end - current.start() > max_duration {
+                parts.push(current);
+                current = self.with_chapters(Vec::new());
+            }
+            current.push(chapter.clone());
+        }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+        parts
+    }
+
+    pub fn rebased(&self) -> ChapterList {
+        let offset = self.start();
+        self.with_chapters(
+            self.chapters
+                .iter()
+                .map(|chapter| {
+                    Chapter::new(
+                        chapter.title(),
+                        chapter.start - offset,
+                        chapter.end - offset,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn with_chapters(&self, chapters: Vec<Chapter>) -> ChapterList {
+        ChapterList {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            narrator: self.narrator.clone(),
+            genre: self.genre.clone(),
+            metadata: self.metadata.clone(),
+            chapters,
+        }
+    }
+
     pub fn toml(&self) -> Result<String> {
-        toml::to_string(self).map_err(|err| Error::TomlSerializationError(err))
+        toml::to_string(self).map_err(Error::TomlSerializationError)
     }
 
     pub fn iter(&self) -> Iter<'_, Chapter> {
@@ -227,10 +284,54 @@ impl ChapterList {
         self.author.clone()
     }
 
+    pub fn narrator(&self) -> Option<String> {
+        self.narrator.clone()
+    }
+
+    pub fn set_narrator(&mut self, narrator: impl Into<String>) {
+        self.narrator = Some(narrator.into());
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    pub fn genre(&self) -> Option<String> {
+        self.genre.clone()
+    }
+
+    pub fn set_genre(&mut self, genre: impl Into<String>) {
+        self.genre = Some(genre.into());
+    }
+
+    pub fn metadata(&self) -> &BookMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: BookMetadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn start(&self) -> u32 {
+        self.chapters.first().map_or(0, Chapter::start)
+    }
+
+    pub fn end(&self) -> u32 {
+        self.chapters.last().map_or(0, Chapter::end)
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.end() - self.start()
+    }
+
     pub fn len(&self) -> usize {
         self.chapters.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty()
+    }
+
     pub fn push(&mut self, new_chapter: Chapter) {
         self.chapters.push(new_chapter);
     }
@@ -269,6 +370,6 @@ impl Display for ChapterList {
         for chapter in &self.chapters {
             table.add_row(row![chapter.title(), chapter.start(), chapter.end()]);
         }
-        write!(f, "{}", table.to_string())
+        write!(f, "{}", table)
     }
 }
@@ -1,205 +1,835 @@
 use crate::errors::*;
+use backend::{JobInput, MediaBackend, TranscodeJob};
 use chapters::ChapterList;
-use clap::parser::ValuesRef;
+use encoder::{Codec, EncoderSettings, EncodingPath};
+use filename::{apply_to_file_tags, apply_to_tag, FilenamePattern, RenameTemplate};
+use frames::{parse_selectors, FrameEdit, FrameSelector};
 use helper::*;
-use id3::{Tag, TagLike};
-use prettytable::{row, Table};
+use id3::{Frame, Tag, TagLike};
+use loudness::{LoudnessMeasurement, LoudnessTarget};
+use metadata::{BookMetadata, BOOK_FIELDS};
+use numbering::{assign_numbers, TitleTemplate};
+use options::{BatchMode, CombineOptions, NumberingOptions, PartOptions, SplitOptions};
+use processing::{InputFile, SegmentEncoder};
+use progress::Event;
+use reports::{
+    BatchReport, BatchResult, CombineReport, DoctorReport, FileTags, LoudnessReport, RenamePlan,
+    SplitPart, TagConflict, TagReport,
+};
 use std::{
-    collections::BTreeSet,
-    fs::File,
-    io::{Read, Write},
-    path::PathBuf,
+    collections::HashSet,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
 };
-use tempfile::NamedTempFile;
-
-use crate::{chapters, helper};
-
-pub fn show_tags(paths: ValuesRef<String>) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = helper::expand_wildcards(paths)?;
-    let mut table = Table::new();
-    table.set_titles(row![
-        b->"File",
-        b->"Title",
-        b->"Album",
-        b->"Author\n(Artist)",
-        b->"Album Artist",
-        b->"Narrator\n(Composer)",
-        b->"Disc",
-        b->"Track",
-    ]);
-
-    for path in &paths {
-        let tag = Tag::read_from_path(&path).unwrap_or(Tag::new());
-        let file_name: &str = match path.file_name() {
-            Some(file_name) => &file_name.to_string_lossy(),
-            None => return Err(Error::NoFilesFountError),
-        };
-        let composer: &str = match tag.get("TCOM") {
-            Some(frame) => frame.content().text().unwrap(),
-            None => "",
-        };
-        let disc: String = match tag.disc() {
-            Some(disc) => disc.to_string(),
-            None => "".to_string(),
-        };
-        let track: String = match tag.track() {
-            Some(track) => track.to_string(),
-            None => "".to_string(),
-        };
+use tempfile::TempDir;
+use tools::{available_encoders, tool_version, ToolConfig, ToolLocator};
 
-        table.add_row(row![
-            file_name,
-            tag.title().unwrap_or(""),
-            tag.album().unwrap_or(""),
-            tag.artist().unwrap_or(""),
-            tag.album_artist().unwrap_or(""),
-            composer,
-            disc,
-            track,
-        ]);
-    }
-    table.printstd();
-    Ok(())
+use crate::{
+    backend, chapters, encoder, filename, frames, helper, loudness, metadata, numbering, options,
+    processing, progress, reports, tools,
+};
+
+pub fn show_tags(paths: impl IntoIterator<Item = impl AsRef<Path>>) -> Result<TagReport> {
+    let paths: Vec<PathBuf> = helper::expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let mut report = TagReport::default();
+
+    for path in paths {
+        report.files.push(file_tags(path));
+    }
+    Ok(report)
 }
 
-pub fn number_files(paths: ValuesRef<String>, start: u32) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+fn file_tags(path: PathBuf) -> FileTags {
+    let tag = read_tag(&path).unwrap_or_default();
+    FileTags {
+        title: tag.title().map(str::to_string),
+        album: tag.album().map(str::to_string),
+        artist: tag.artist().map(str::to_string),
+        album_artist: tag.album_artist().map(str::to_string),
+        composer: tag
+            .get("TCOM")
+            .and_then(|frame| frame.content().text())
+            .map(str::to_string),
+        disc: tag.disc(),
+        track: tag.track(),
+        metadata: BookMetadata::from_tag(&tag),
+        path,
+    }
+}
 
-    for (path, i) in paths.iter().zip(start..) {
-        let mut tag: Tag = read_tag(path)?;
-        tag.set_track(i);
-        if let Err(err) = tag.write_to_path(path, id3::Version::Id3v23) {
-            return Err(Error::Id3Error(err));
+pub fn preview_tags_from_filename(
+    pattern: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+) -> Result<TagReport> {
+    let pattern = FilenamePattern::parse(pattern)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let mut report = TagReport::default();
+
+    for path in paths {
+        let values = pattern.match_path(&path)?;
+        let mut file_tags = file_tags(path);
+        apply_to_file_tags(&mut file_tags, &values);
+        report.files.push(file_tags);
+    }
+    Ok(report)
+}
+
+pub fn tags_from_filename(
+    pattern: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let pattern = FilenamePattern::parse(pattern)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    if mode == BatchMode::FailFast {
+        for path in &paths {
+            pattern.match_path(path)?;
         }
     }
-    Ok(())
+
+    edit_files(paths, mode, |path, _| {
+        let values = pattern.match_path(path)?;
+        let mut tag = read_tag(path)?;
+        apply_to_tag(&mut tag, &values);
+        save_tag(path, &tag)
+    })
 }
 
-pub fn number_chapters(naming_scheme: &str, paths: ValuesRef<String>, start: i32) -> Result<()> {
-    if !naming_scheme.contains("%n") {
-        return Err(Error::NoFormatSpecifierError("%n".to_string()));
+pub fn number_files(
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    options: &NumberingOptions,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let numbers = assign_numbers(&paths, options)?;
+
+    edit_files(paths, mode, |path, i| {
+        let number = numbers[i];
+        let mut tag = read_tag(path)?;
+        tag.set_track(number.track);
+        if let Some(total_tracks) = number.total_tracks {
+            tag.set_total_tracks(total_tracks);
+        }
+        if let Some(disc) = number.disc {
+            tag.set_disc(disc);
+        }
+        if let Some(total_discs) = number.total_discs {
+            tag.set_total_discs(total_discs);
+        }
+        save_tag(path, &tag)
+    })
+}
+
+pub fn number_chapters(
+    naming_scheme: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    options: &NumberingOptions,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let template = TitleTemplate::parse(naming_scheme)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let numbers = assign_numbers(&paths, &options.clone().totals(true))?;
+
+    edit_files(paths, mode, |path, i| {
+        let tag = read_tag(path)?;
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let chapter_name =
+            template.render(&numbers[i], tag.title().unwrap_or_default(), &file_stem);
+        write_tag(path, "TIT2", &chapter_name)
+    })
+}
+
+pub fn plan_renames(
+    template: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    output_dir: impl AsRef<Path>,
+) -> Result<RenamePlan> {
+    let template = RenameTemplate::parse(template)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let output_dir = output_dir.as_ref();
+    let mut plan = RenamePlan::default();
+    let mut targets: HashSet<String> = HashSet::new();
+
+    for path in paths {
+        let tag = read_tag(&path)?;
+        let target = output_dir.join(template.render(&path, &tag)?);
+        if !targets.insert(target.to_string_lossy().to_lowercase()) {
+            return Err(Error::RenameCollisionError { path: target });
+        }
+        let same_file = target.canonicalize().is_ok_and(|existing| existing == path);
+        if target.exists() && !same_file {
+            return Err(Error::RenameCollisionError { path: target });
+        }
+        if !same_file {
+            plan.moves.push((path, target));
+        }
     }
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+    Ok(plan)
+}
 
-    for (path, i) in paths.iter().zip(start..) {
-        let chapter_name = naming_scheme.replace("%n", &i.to_string());
-        write_tag(path, "TIT2", &chapter_name)?;
+pub fn apply_renames(plan: &RenamePlan) -> Result<()> {
+    for (from, to) in &plan.moves {
+        if to.exists() {
+            return Err(Error::RenameCollisionError { path: to.clone() });
+        }
+        let file_error = |source| Error::FileError {
+            path: to.clone(),
+            source,
+        };
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        if fs::rename(from, to).is_err() {
+            fs::copy(from, to).map_err(file_error)?;
+            fs::remove_file(from).map_err(|source| Error::FileError {
+                path: from.clone(),
+                source,
+            })?;
+        }
     }
     Ok(())
 }
 
-pub fn change_title(title: &str, paths: ValuesRef<String>) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+pub fn change_title(
+    title: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    change_tag("TIT2", title, paths, mode)
+}
 
-    for path in &paths {
-        write_tag(path, "TIT2", title)?;
-    }
-    Ok(())
+pub fn change_author(
+    author: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    change_tag("TPE1", author, paths, mode)
 }
 
-pub fn change_author(author: &str, paths: ValuesRef<String>) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+pub fn change_narrator(
+    narrator: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    change_tag("TCOM", narrator, paths, mode)
+}
 
-    for path in &paths {
-        write_tag(path, "TPE1", author)?;
-    }
-    Ok(())
+pub fn change_book_metadata(
+    metadata: &BookMetadata,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+
+    edit_files(paths, mode, |path, _| {
+        let mut tag = read_tag(path)?;
+        metadata.write_to_tag(&mut tag);
+        save_tag(path, &tag)
+    })
+}
+
+pub fn change_tag(
+    frame: &str,
+    new_text: &str,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    change_frame(&FrameEdit::parse(frame, new_text, None)?, paths, mode)
+}
+
+pub fn change_frame(
+    edit: &FrameEdit,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+
+    edit_files(paths, mode, |path, _| {
+        let mut tag = read_tag(path)?;
+        edit.apply(&mut tag);
+        save_tag(path, &tag)
+    })
+}
+
+pub fn remove_tag(
+    frames: impl IntoIterator<Item = impl AsRef<str>>,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let selectors: Vec<FrameSelector> = parse_selectors(frames)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+
+    edit_files(paths, mode, |path, _| {
+        retain_frames(path, |frame| {
+            !selectors.iter().any(|selector| selector.matches(frame))
+        })
+    })
 }
 
-pub fn change_narrator(narrator: &str, paths: ValuesRef<String>) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+pub fn clear_tags(
+    keep: impl IntoIterator<Item = impl AsRef<str>>,
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let selectors: Vec<FrameSelector> = parse_selectors(keep)?;
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+    let check = |path: &Path| match selectors.is_empty() {
+        true => check_writable(path),
+        false => check_editable(path),
+    };
+
+    edit_files_checked(paths, mode, check, |path, _| {
+        if selectors.is_empty() {
+            remove_id3v2(path)?;
+        } else {
+            retain_frames(path, |frame| {
+                selectors.iter().any(|selector| selector.matches(frame))
+            })?;
+        }
+        remove_id3v1(path)
+    })
+}
+
+pub fn strip_v1(
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    mode: BatchMode,
+) -> Result<BatchReport> {
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &ID3_EXTENSIONS)?;
+
+    edit_files_checked(
+        paths,
+        mode,
+        |path| check_writable(path),
+        |path, _| remove_id3v1(path),
+    )
+}
 
-    for path in &paths {
-        write_tag(path, "TCOM", narrator)?;
+fn retain_frames(path: &Path, keep: impl Fn(&Frame) -> bool) -> Result<()> {
+    let tag = read_tag(path)?;
+    if tag.frames().all(&keep) {
+        return Ok(());
     }
-    Ok(())
+    let mut retained = Tag::with_version(tag.version());
+    for frame in tag.frames().filter(|frame| keep(frame)) {
+        retained.add_frame(frame.clone());
+    }
+    if retained.frames().next().is_none() {
+        return remove_id3v2(path);
+    }
+    save_tag(path, &retained)
 }
 
-pub fn change_tag(frame_id: &str, new_text: &str, paths: ValuesRef<String>) -> Result<()> {
-    let paths: BTreeSet<PathBuf> = expand_wildcards(paths)?;
+fn edit_files(
+    paths: impl IntoIterator<Item = PathBuf>,
+    mode: BatchMode,
+    edit: impl FnMut(&Path, usize) -> Result<()>,
+) -> Result<BatchReport> {
+    edit_files_checked(paths, mode, |path| check_editable(path), edit)
+}
 
-    for path in &paths {
-        write_tag(path, frame_id, new_text)?;
+fn edit_files_checked(
+    paths: impl IntoIterator<Item = PathBuf>,
+    mode: BatchMode,
+    check: impl Fn(&Path) -> Result<()>,
+    mut edit: impl FnMut(&Path, usize) -> Result<()>,
+) -> Result<BatchReport> {
+    let paths: Vec<PathBuf> = paths.into_iter().collect();
+    if mode == BatchMode::FailFast {
+        for path in &paths {
+            check(path)?;
+        }
     }
-    Ok(())
+
+    let mut report = BatchReport::default();
+    for (i, path) in paths.into_iter().enumerate() {
+        let result = edit(&path, i);
+        match result {
+            Err(err) if mode == BatchMode::FailFast => return Err(err),
+            result => report.files.push(BatchResult {
+                path,
+                error: result.err(),
+            }),
+        }
+    }
+    Ok(report)
 }
 
 pub fn combine_files(
-    paths: ValuesRef<String>,
-    output: &str,
-    bitrate: u32,
-    title: &str,
-    author: &str,
-    ffmpeg_path: &str,
-) -> Result<()> {
-    let paths = expand_wildcards(paths)?;
-    let file_tmp_buf: String = paths
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    output: impl AsRef<Path>,
+    options: &CombineOptions,
+    backend: &dyn MediaBackend,
+) -> Result<CombineReport> {
+    let encoder = &options.encoder;
+    let paths = expand_wildcards(paths, &AUDIO_EXTENSIONS)?;
+    let mut inputs: Vec<InputFile> = paths
         .iter()
-        .map(|path| format!("file '{}'", path.to_string_lossy()))
-        .collect::<Vec<String>>()
-        .join("\n");
-    let mut files_tmp = NamedTempFile::new()?;
-    files_tmp.write_all(file_tmp_buf.as_bytes())?;
-    let files_tmp_path = files_tmp.path().to_string_lossy();
-
-    let mut ffmetadata_tmp = NamedTempFile::new()?;
-    // let ffmetadata: String = generate_metadata(&paths, title, author)?;
-    let chapter_list = ChapterList::from_path_set(paths, title.to_string(), author.to_string())?;
-    let ffmetadata = chapter_list.ffmetadata();
-    ffmetadata_tmp.write_all(ffmetadata.as_bytes())?;
-    let ffmetadata_tmp_path = ffmetadata_tmp.path().to_string_lossy();
-
-    let bitrate = format!("{bitrate}k");
-
-    let arguments = [
-        "-f",
-        "concat",
-        "-safe",
-        "0",
-        "-i",
-        &files_tmp_path,
-        "-i",
-        &ffmetadata_tmp_path,
-        "-map_metadata",
-        "1",
-        "-c:a",
-        "aac",
-        "-b:a",
-        &bitrate,
-        output,
-    ];
-    run_ffmpeg(ffmpeg_path, arguments)
-}
-
-pub fn show_chapters(path: &str) -> Result<()> {
-    let chapter_list = ChapterList::from_chaptered_file(path)?;
-    println!("{}", chapter_list);
-    Ok(())
+        .map(|path| InputFile::new(path, backend))
+        .collect::<Result<Vec<InputFile>>>()?;
+    if let Some(silence) = &options.silence {
+        for input in &mut inputs {
+            backend.report(Event::Status(format!(
+                "Detecting silence in {}",
+                input.path().to_string_lossy()
+            )));
+            input.trim_silence(silence, backend)?;
+        }
+    }
+    if let Some(target) = &options.normalization {
+        for input in &mut inputs {
+            backend.report(Event::Status(format!(
+                "Measuring loudness of {}",
+                input.path().to_string_lossy()
+            )));
+            input.normalize_loudness(target, backend)?;
+        }
+    }
+    if options.gap > 0 {
+        let last = inputs.len() - 1;
+        for input in &mut inputs[..last] {
+            input.set_gap(options.gap);
+        }
+    }
+    let processed = inputs.iter().any(InputFile::is_processed);
+
+    let copy = encoder.codec() == Codec::Copy;
+    let encoding = if copy && processed {
+        return Err(Error::EncoderSettingsError(
+            "silence trimming, gaps and loudness normalization cannot be used \
+            when stream copying"
+                .to_string(),
+        ));
+    } else if processed {
+        EncodingPath::Reencode("the inputs are trimmed, padded or loudness normalized".to_string())
+    } else if options.explicit_quality && !copy {
+        EncodingPath::Reencode("a bitrate or quality was given explicitly".to_string())
+    } else if copy || options.allow_stream_copy {
+        let inputs = paths
+            .iter()
+            .map(|path| Ok((path.clone(), backend.probe(path)?.audio()?.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        encoder.encoding_path(&inputs)?
+    } else {
+        EncodingPath::Reencode("stream copy was disabled".to_string())
+    };
+    let stream_copy = match &encoding {
+        EncodingPath::StreamCopy(reason) => {
+            backend.report(Event::Status(format!(
+                "Stream copying without re-encoding: {reason}"
+            )));
+            true
+        }
+        EncodingPath::Reencode(reason) => {
+            backend.report(Event::Status(format!("Re-encoding: {reason}")));
+            false
+        }
+    };
+    let segmented = (options.jobs > 1 || options.work_dir.is_some()) && !stream_copy;
+
+    let (chapter_list, conflicts) = combined_chapter_list(&inputs, options, backend)?;
+    let tmp_work_dir = TempDir::new()?;
+    let work_dir = options
+        .work_dir
+        .clone()
+        .unwrap_or(tmp_work_dir.path().to_path_buf());
+    let mut job = if segmented {
+        let media_info = backend.probe(inputs[0].path())?;
+        let first = media_info.audio()?;
+        let mut segment_settings = encoder.clone();
+        segment_settings.set_channels(encoder.channels().unwrap_or(first.channels));
+        let segment_encoder = SegmentEncoder::new(
+            segment_settings,
+            encoder.sample_rate().unwrap_or(first.sample_rate),
+            backend,
+        );
+        backend.report(Event::Status(format!(
+            "Encoding {} files with {} parallel job(s) in {}",
+            inputs.len(),
+            options.jobs,
+            work_dir.to_string_lossy()
+        )));
+        let segments = segment_encoder.encode_all(&inputs, &work_dir, options.jobs)?;
+        TranscodeJob::new(
+            segments.into_iter().map(JobInput::new).collect(),
+            EncoderSettings::new(Codec::Copy),
+            output.as_ref(),
+        )
+    } else if processed {
+        let sample_rate = match encoder.sample_rate() {
+            Some(sample_rate) => sample_rate,
+            None => backend.probe(inputs[0].path())?.audio()?.sample_rate,
+        };
+        TranscodeJob::new(
+            inputs
+                .iter()
+                .map(|input| JobInput::filtered(input.path(), input.filter_chain(sample_rate)))
+                .collect(),
+            encoder.clone(),
+            output.as_ref(),
+        )
+    } else {
+        let encoder = match stream_copy {
+            true => EncoderSettings::new(Codec::Copy),
+            false => encoder.clone(),
+        };
+        TranscodeJob::new(
+            paths.iter().map(JobInput::new).collect(),
+            encoder,
+            output.as_ref(),
+        )
+    };
+    job.chapters = Some(chapter_list.clone());
+    job.duration = Some(chapter_list.end());
+    backend.transcode(&job)?;
+    Ok(CombineReport {
+        output: output.as_ref().to_path_buf(),
+        chapters: chapter_list,
+        encoding,
+        conflicts,
+    })
 }
 
-pub fn chapters_to_toml(path: &str) -> Result<()> {
-    let chapter_toml = ChapterList::from_chaptered_file(path)?.toml()?;
-    print!("{}", &chapter_toml);
+fn combined_chapter_list(
+    inputs: &[InputFile],
+    options: &CombineOptions,
+    backend: &dyn MediaBackend,
+) -> Result<(ChapterList, Vec<TagConflict>)> {
+    let tags: Vec<Tag> = inputs
+        .iter()
+        .map(|input| read_tag(input.path()))
+        .collect::<Result<_>>()?;
+    let mut conflicts: Vec<TagConflict> = Vec::new();
+    let title = consensus(
+        "title",
+        options.title.clone(),
+        tags.iter().map(|tag| tag.album().map(str::to_string)),
+        &mut conflicts,
+    );
+    let author = consensus(
+        "author",
+        options.author.clone(),
+        tags.iter()
+            .map(|tag| tag.artist().or(tag.album_artist()).map(str::to_string)),
+        &mut conflicts,
+    );
+    let narrator = consensus(
+        "narrator",
+        options.narrator.clone(),
+        tags.iter().map(|tag| {
+            tag.get("TCOM")
+                .and_then(|frame| frame.content().text())
+                .map(str::to_string)
+        }),
+        &mut conflicts,
+    );
+    let genre = consensus(
+        "genre",
+        options.genre.clone(),
+        tags.iter()
+            .map(|tag| tag.genre_parsed().map(|genre| genre.into_owned())),
+        &mut conflicts,
+    );
+    let sources: Vec<BookMetadata> = tags.iter().map(BookMetadata::from_tag).collect();
+    let mut metadata = BookMetadata::new();
+    for field in BOOK_FIELDS {
+        let value = consensus(
+            field,
+            options.metadata.field(field),
+            sources.iter().map(|source| source.field(field)),
+            &mut conflicts,
+        );
+        if let Some(value) = value {
+            metadata.set_field(field, value);
+        }
+    }
 
-    Ok(())
+    let mut chapter_list = ChapterList::from_path_set(
+        inputs,
+        title.unwrap_or("Unknown title".to_string()),
+        author.unwrap_or("Unknown author".to_string()),
+        backend,
+    )?;
+    if let Some(narrator) = narrator {
+        chapter_list.set_narrator(narrator);
+    }
+    if let Some(genre) = genre {
+        chapter_list.set_genre(genre);
+    }
+    chapter_list.set_metadata(metadata);
+    Ok((chapter_list, conflicts))
+}
+
+fn consensus(
+    field: &'static str,
+    preset: Option<String>,
+    values: impl IntoIterator<Item = Option<String>>,
+    conflicts: &mut Vec<TagConflict>,
+) -> Option<String> {
+    if preset.is_some() {
+        return preset;
+    }
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for value in values.into_iter().flatten() {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match counts.iter_mut().find(|(known, _)| known == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value.to_string(), 1)),
+        }
+    }
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    if counts.len() > 1 {
+        conflicts.push(TagConflict {
+            field,
+            values: counts.clone(),
+        });
+    }
+    counts.into_iter().next().map(|(value, _)| value)
+}
+
+pub fn analyze_loudness(
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    target: &LoudnessTarget,
+    backend: &dyn MediaBackend,
+) -> Result<LoudnessReport> {
+    let paths: Vec<PathBuf> = expand_wildcards(paths, &AUDIO_EXTENSIONS)?;
+    let mut measurements: Vec<(PathBuf, LoudnessMeasurement)> = Vec::new();
+    for path in paths {
+        let measurement = LoudnessMeasurement::measure(&path, target, backend)?;
+        measurements.push((path, measurement));
+    }
+    Ok(LoudnessReport {
+        target: *target,
+        measurements,
+    })
+}
+
+pub fn show_chapters(path: impl AsRef<Path>, backend: &dyn MediaBackend) -> Result<ChapterList> {
+    ChapterList::from_chaptered_file(path, backend)
+}
+
+pub fn chapters_to_toml(path: impl AsRef<Path>, backend: &dyn MediaBackend) -> Result<String> {
+    ChapterList::from_chaptered_file(path, backend)?.toml()
 }
 
 pub fn toml_to_chapters(
-    path: &str,
-    output: &str,
-    toml_path: &str,
-    ffmpeg_path: &str,
-) -> Result<()> {
+    path: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    toml_path: impl AsRef<Path>,
+    backend: &dyn MediaBackend,
+) -> Result<ChapterList> {
     let mut toml = String::new();
-    File::open(toml_path)?.read_to_string(&mut toml)?;
-    ChapterList::from_toml(&toml)?.write_to_file(path, output, ffmpeg_path)?;
+    File::open(&toml_path)
+        .and_then(|mut file| file.read_to_string(&mut toml))
+        .map_err(|source| Error::FileError {
+            path: toml_path.as_ref().to_path_buf(),
+            source,
+        })?;
+    let chapter_list = ChapterList::from_toml(&toml)?;
+    chapter_list.write_to_file(path, output, backend)?;
 
-    Ok(())
+    Ok(chapter_list)
+}
+
+pub fn split_file(
+    path: impl AsRef<Path>,
+    options: &SplitOptions,
+    backend: &dyn MediaBackend,
+) -> Result<Vec<PathBuf>> {
+    expand_naming_scheme(&options.naming_scheme, &[('n', ""), ('b', ""), ('t', "")])?;
+    let chapter_list = ChapterList::from_chaptered_file(&path, backend)?;
+    if chapter_list.is_empty() {
+        return Err(Error::NoChaptersError {
+            path: path.as_ref().to_path_buf(),
+        });
+    }
+    let book_title = options.title.clone().unwrap_or(chapter_list.title());
+    let author = options.author.clone().unwrap_or(chapter_list.author());
+    let narrator = options.narrator.clone().or(chapter_list.narrator());
+    let encoder = options.encoder();
+    encoder.ffmpeg_arguments()?;
+
+    fs::create_dir_all(&options.output_dir).map_err(|source| Error::FileError {
+        path: options.output_dir.clone(),
+        source,
+    })?;
+    let total = chapter_list.len();
+    let width = total.to_string().len();
+    let mut outputs: Vec<PathBuf> = Vec::new();
+
+    for (chapter, number) in chapter_list.iter().zip(1..) {
+        let file_name = expand_naming_scheme(
+            &options.naming_scheme,
+            &[
+                ('n', &format!("{number:0width$}")),
+                ('b', &book_title),
+                ('t', &chapter.title()),
+            ],
+        )?;
+        let output_path = options.output_dir.join(format!(
+            "{}.{}",
+            sanitize_file_name(&file_name),
+            encoder.codec().extension()
+        ));
+
+        let mut job = TranscodeJob::new(
+            vec![JobInput::new(path.as_ref())],
+            encoder.clone(),
+            &output_path,
+        );
+        job.range = Some((chapter.start(), chapter.end()));
+        job.duration = Some(chapter.end() - chapter.start());
+        job.metadata = vec![
+            ("title".to_string(), chapter.title()),
+            ("album".to_string(), book_title.clone()),
+            ("artist".to_string(), author.clone()),
+            ("album_artist".to_string(), author.clone()),
+            (
+                "genre".to_string(),
+                chapter_list.genre().unwrap_or("Audiobook".to_string()),
+            ),
+            ("track".to_string(), format!("{number}/{total}")),
+        ];
+        if let Some(narrator) = &narrator {
+            job.metadata
+                .push(("composer".to_string(), narrator.clone()));
+        }
+        backend.transcode(&job)?;
+        outputs.push(output_path.clone());
+    }
+    Ok(outputs)
+}
+
+pub fn split_parts(
+    path: impl AsRef<Path>,
+    options: &PartOptions,
+    backend: &dyn MediaBackend,
+) -> Result<Vec<SplitPart>> {
+    expand_naming_scheme(&options.naming_scheme, &[('n', ""), ('b', "")])?;
+    let chapter_list = ChapterList::from_chaptered_file(&path, backend)?;
+    if chapter_list.is_empty() {
+        return Err(Error::NoChaptersError {
+            path: path.as_ref().to_path_buf(),
+        });
+    }
+
+    let mut max_milliseconds: u32 = options
+        .max_duration
+        .map_or(u32::MAX, |minutes| minutes.saturating_mul(60_000));
+    if let Some(megabytes) = options.max_size {
+        let bytes_per_millisecond =
+            file_size(path.as_ref())? as f64 / chapter_list.end().max(1) as f64;
+        let size_limit = (megabytes * 1_000_000) as f64 / bytes_per_millisecond;
+        max_milliseconds = max_milliseconds.min(size_limit as u32);
+    }
+
+    fs::create_dir_all(&options.output_dir).map_err(|source| Error::FileError {
+        path: options.output_dir.clone(),
+        source,
+    })?;
+    let parts = chapter_list.split_into_parts(max_milliseconds);
+    let width = parts.len().to_string().len();
+    let mut outputs: Vec<SplitPart> = Vec::new();
+
+    for (part, number) in parts.iter().zip(1..) {
+        let mut part_chapters = part.rebased();
+        part_chapters.set_title(format!("{} (Part {number})", chapter_list.title()));
+
+        let file_name = expand_naming_scheme(
+            &options.naming_scheme,
+            &[
+                ('n', &format!("{number:0width$}")),
+                ('b', &chapter_list.title()),
+            ],
+        )?;
+        let output_path = options
+            .output_dir
+            .join(format!("{}.m4b", sanitize_file_name(&file_name)));
+
+        let mut job = TranscodeJob::new(
+            vec![JobInput::new(path.as_ref())],
+            EncoderSettings::new(Codec::Copy),
+            &output_path,
+        );
+        job.range = Some((part.start(), part.end()));
+        job.chapters = Some(part_chapters.clone());
+        job.duration = Some(part.duration());
+        backend.transcode(&job)?;
+        outputs.push(SplitPart {
+            path: output_path.clone(),
+            exceeds_limit: part.duration() > max_milliseconds,
+            chapters: part_chapters,
+        });
+    }
+    Ok(outputs)
 }
 
-pub fn example_toml() {
-    print!(
-        "title = \"Example title\"
+fn expand_naming_scheme(naming_scheme: &str, values: &[(char, &str)]) -> Result<String> {
+    let mut expanded = String::new();
+    let mut numbered = false;
+    let mut chars = naming_scheme.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some(specifier) => match values.iter().find(|(key, _)| *key == specifier) {
+                Some((_, value)) => {
+                    numbered |= specifier == 'n';
+                    expanded.push_str(value);
+                }
+                None => return Err(Error::UnknownFormatSpecifierError(format!("%{specifier}"))),
+            },
+            None => return Err(Error::UnknownFormatSpecifierError("%".to_string())),
+        }
+    }
+    if !numbered {
+        return Err(Error::NoFormatSpecifierError("%n".to_string()));
+    }
+    Ok(expanded)
+}
+
+fn file_size(path: &Path) -> Result<u64> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(source) => Err(Error::FileError {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+pub fn doctor(ffmpeg_override: Option<&str>) -> Result<DoctorReport> {
+    let locator = ToolLocator::locate(ffmpeg_override)?;
+    let encoders = available_encoders(locator.ffmpeg_path()).unwrap_or_default();
+    let problem = locator
+        .check_ffmpeg(&[Codec::Aac.encoder()])
+        .and_then(|_| locator.check_ffprobe())
+        .err();
+
+    Ok(DoctorReport {
+        config_path: ToolConfig::path(),
+        ffmpeg_version: tool_version(locator.ffmpeg_path()).ok(),
+        ffprobe_version: tool_version(locator.ffprobe_path()).ok(),
+        encoders: [Codec::Aac, Codec::FdkAac, Codec::Opus, Codec::Mp3]
+            .into_iter()
+            .map(|codec| {
+                let available = encoders.iter().any(|encoder| encoder == codec.encoder());
+                (codec, available)
+            })
+            .collect(),
+        problem,
+        locator,
+    })
+}
+
+pub fn example_toml() -> &'static str {
+    "title = \"Example title\"
 author = \"Example author\"
 
 [[chapters]]
@@ -216,6 +846,5 @@ end = 2000
 title = \"Chapter 3\"
 start = 2000
 end = 3000
-        "
-    )
+"
 }
@@ -0,0 +1,292 @@
+use crate::{backend::AudioInfo, Error, Result};
+use std::{path::PathBuf, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Aac,
+    FdkAac,
+    Opus,
+    Mp3,
+    Copy,
+}
+
+impl Codec {
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            Codec::Aac => "aac",
+            Codec::FdkAac => "libfdk_aac",
+            Codec::Opus => "libopus",
+            Codec::Mp3 => "libmp3lame",
+            Codec::Copy => "copy",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Codec::Aac | Codec::FdkAac => "m4a",
+            Codec::Opus => "opus",
+            Codec::Mp3 => "mp3",
+            Codec::Copy => "mka",
+        }
+    }
+
+    pub fn codec_name(&self) -> Option<&'static str> {
+        match self {
+            Codec::Aac | Codec::FdkAac => Some("aac"),
+            Codec::Opus => Some("opus"),
+            Codec::Mp3 => Some("mp3"),
+            Codec::Copy => None,
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    fn from_str(codec: &str) -> Result<Self> {
+        match codec.to_lowercase().as_str() {
+            "aac" | "m4a" => Ok(Codec::Aac),
+            "libfdk_aac" | "fdk-aac" | "fdk_aac" => Ok(Codec::FdkAac),
+            "opus" | "libopus" => Ok(Codec::Opus),
+            "mp3" | "libmp3lame" => Ok(Codec::Mp3),
+            "copy" => Ok(Codec::Copy),
+            _ => Err(Error::UnsupportedCodecError(codec.to_string())),
+        }
+    }
+}
+
+pub const PRESETS: [&str; 4] = ["speech-low", "speech", "standard", "archival"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncoderSettings {
+    codec: Codec,
+    bitrate: Option<u32>,
+    quality: Option<f32>,
+    channels: Option<u32>,
+    sample_rate: Option<u32>,
+}
+
+impl EncoderSettings {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            bitrate: None,
+            quality: None,
+            channels: None,
+            sample_rate: None,
+        }
+    }
+
+    pub fn preset(name: &str) -> Result<Self> {
+        let mut settings = EncoderSettings::new(Codec::Aac);
+        match name {
+            "speech-low" => {
+                settings.bitrate = Some(32);
+                settings.channels = Some(1);
+                settings.sample_rate = Some(22050);
+            }
+            "speech" => {
+                settings.bitrate = Some(64);
+                settings.channels = Some(1);
+                settings.sample_rate = Some(44100);
+            }
+            "standard" => {
+                settings.bitrate = Some(64);
+            }
+            "archival" => {
+                settings.bitrate = Some(192);
+            }
+            _ => return Err(Error::UnknownPresetError(name.to_string())),
+        }
+        Ok(settings)
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn bitrate(&self) -> Option<u32> {
+        self.bitrate
+    }
+
+    pub fn quality(&self) -> Option<f32> {
+        self.quality
+    }
+
+    pub fn channels(&self) -> Option<u32> {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u32) {
+        self.bitrate = Some(bitrate);
+        self.quality = None;
+    }
+
+    pub fn set_quality(&mut self, quality: f32) {
+        self.quality = Some(quality);
+        self.bitrate = None;
+    }
+
+    pub fn set_channels(&mut self, channels: u32) {
+        self.channels = Some(channels);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = Some(sample_rate);
+    }
+
+    pub fn ffmpeg_arguments(&self) -> Result<Vec<String>> {
+        let mut arguments = vec!["-c:a".to_string(), self.codec.encoder().to_string()];
+
+        if self.codec == Codec::Copy {
+            if self.channels.is_some() || self.sample_rate.is_some() {
+                return Err(Error::EncoderSettingsError(
+                    "channels and sample rate cannot be changed when stream copying".to_string(),
+                ));
+            }
+            return Ok(arguments);
+        }
+
+        if let Some(quality) = self.quality {
+            let quality_arguments = match self.codec {
+                Codec::Aac | Codec::Mp3 => ["-q:a".to_string(), quality.to_string()],
+                Codec::FdkAac => ["-vbr".to_string(), (quality.round() as u32).to_string()],
+                _ => {
+                    return Err(Error::EncoderSettingsError(format!(
+                        "{} does not support VBR quality, use a bitrate instead",
+                        self.codec.encoder()
+                    )))
+                }
+            };
+            arguments.extend(quality_arguments);
+        } else if let Some(bitrate) = self.bitrate {
+            arguments.extend(["-b:a".to_string(), format!("{bitrate}k")]);
+        }
+        if let Some(channels) = self.channels {
+            arguments.extend(["-ac".to_string(), channels.to_string()]);
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            arguments.extend(["-ar".to_string(), sample_rate.to_string()]);
+        }
+        Ok(arguments)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingPath {
+    StreamCopy(String),
+    Reencode(String),
+}
+
+impl EncoderSettings {
+    pub fn encoding_path(&self, inputs: &[(PathBuf, AudioInfo)]) -> Result<EncodingPath> {
+        let copy_error = |reason: String| {
+            Error::EncoderSettingsError(format!(
+                "the copy codec needs AAC inputs with the same sample rate and channel \
+                layout, but {reason}"
+            ))
+        };
+        let Some((_, first)) = inputs.first() else {
+            return match self.codec {
+                Codec::Copy => Err(copy_error("there are no inputs".to_string())),
+                _ => Ok(EncodingPath::Reencode(
+                    "there are no inputs to inspect".to_string(),
+                )),
+            };
+        };
+        if let Some(reason) = input_mismatch(inputs) {
+            return match self.codec {
+                Codec::Copy => Err(copy_error(reason)),
+                _ => Ok(EncodingPath::Reencode(reason)),
+            };
+        }
+        let Some(codec_name) = self.codec.codec_name() else {
+            if first.codec != "aac" {
+                return Err(copy_error(format!("the inputs are {}", first.codec)));
+            }
+            return Ok(EncodingPath::StreamCopy(format!(
+                "the copy codec was selected and all inputs are aac, {} Hz, {} channel(s)",
+                first.sample_rate, first.channels
+            )));
+        };
+
+        if first.codec != codec_name {
+            return Ok(EncodingPath::Reencode(format!(
+                "the inputs are {}, but the output codec is {codec_name}",
+                first.codec
+            )));
+        }
+        if self
+            .channels
+            .is_some_and(|channels| channels != first.channels)
+        {
+            return Ok(EncodingPath::Reencode(format!(
+                "the inputs have {} channel(s), but {} were requested",
+                first.channels,
+                self.channels.unwrap_or_default()
+            )));
+        }
+        if self
+            .sample_rate
+            .is_some_and(|sample_rate| sample_rate != first.sample_rate)
+        {
+            return Ok(EncodingPath::Reencode(format!(
+                "the inputs are {} Hz, but {} Hz was requested",
+                first.sample_rate,
+                self.sample_rate.unwrap_or_default()
+            )));
+        }
+        Ok(EncodingPath::StreamCopy(format!(
+            "all inputs are {}, {} Hz, {} channel(s)",
+            first.codec, first.sample_rate, first.channels
+        )))
+    }
+}
+
+fn input_mismatch(inputs: &[(PathBuf, AudioInfo)]) -> Option<String> {
+    let (first_path, first) = inputs.first()?;
+    for (path, info) in inputs {
+        let file_name = path.to_string_lossy();
+        if info.codec != first.codec {
+            return Some(format!(
+                "{file_name} is {}, but {} is {}",
+                info.codec,
+                first_path.to_string_lossy(),
+                first.codec
+            ));
+        }
+        if info.sample_rate != first.sample_rate {
+            return Some(format!(
+                "{file_name} is {} Hz, but {} is {} Hz",
+                info.sample_rate,
+                first_path.to_string_lossy(),
+                first.sample_rate
+            ));
+        }
+        if info.channels != first.channels || info.channel_layout != first.channel_layout {
+            return Some(format!(
+                "{file_name} has {} channel(s) ({}), but {} has {} channel(s) ({})",
+                info.channels,
+                info.channel_layout,
+                first_path.to_string_lossy(),
+                first.channels,
+                first.channel_layout
+            ));
+        }
+    }
+    None
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        EncoderSettings::preset("standard").unwrap()
+    }
+}
@@ -1,4 +1,5 @@
 use mp3_duration::MP3DurationError;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,8 +7,14 @@ pub enum Error {
     #[error("An error occured while reading or writing to file: {0}")]
     IoError(#[from] std::io::Error),
 
-    #[error("An error occured while reading or writing to an id3-tag: {0}")]
-    Id3Error(#[from] id3::Error),
+    #[error("An error occured while accessing {}: {source}", path.display())]
+    FileError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("An error occured while reading or writing the id3-tag of {}: {source}", path.display())]
+    Id3Error { path: PathBuf, source: id3::Error },
 
     #[error("No files matched the provided pattern")]
     NoFilesFountError,
@@ -15,14 +22,29 @@ pub enum Error {
     #[error("An error occured while parsing path wild cards: {0}")]
     GlobError(#[from] glob::PatternError),
 
+    #[error("An error occured while expanding path wild cards: {0}")]
+    GlobIterationError(#[from] glob::GlobError),
+
     #[error("The format string did not contain this format specifier: {0}")]
     NoFormatSpecifierError(String),
 
-    #[error("An error occured while reading MP3 file duration: {0}")]
-    NoDurationError(#[from] MP3DurationError),
+    #[error("An error occured while reading the MP3 duration of {}: {source}", path.display())]
+    NoDurationError {
+        path: PathBuf,
+        source: MP3DurationError,
+    },
 
-    #[error("ffmpeg encountered an error: {0}")]
-    FfmpegError(i32),
+    #[error(
+        "ffmpeg exited with code {code} while processing {input}\n\
+        Command: {command}\n\
+        {stderr_tail}"
+    )]
+    FfmpegError {
+        code: i32,
+        command: String,
+        input: String,
+        stderr_tail: String,
+    },
 
     #[error("Could not find ffmpeg executable: {0}")]
     FfmpegNotFoundError(String),
@@ -30,8 +52,18 @@ pub enum Error {
     #[error("Could not find ffprobe. It is automatically installed with ffmpeg")]
     FfprobeNotFoundError(),
 
-    #[error("An error occured while reading the chapters of the audio file")]
-    ChapterReadError,
+    #[error("{0} is version {1}, but at least version 4 is required")]
+    UnsupportedToolVersionError(String, String),
+
+    #[error("ffmpeg does not have the required encoder: {0}")]
+    MissingEncoderError(String),
+
+    #[error("Chapter {index} of {} has a missing or invalid {field}", path.display())]
+    ChapterReadError {
+        path: PathBuf,
+        index: usize,
+        field: &'static str,
+    },
 
     #[error("Failed write to TOML data: {0}")]
     TomlSerializationError(#[from] toml::ser::Error),
@@ -40,7 +72,70 @@ pub enum Error {
     TomlDeserializationError(#[from] toml::de::Error),
 
     #[error("Filed to read from stdin")]
-    StdinError
+    StdinError,
+
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodecError(String),
+
+    #[error("Unknown encoder preset: {0}")]
+    UnknownPresetError(String),
+
+    #[error("Invalid encoder settings: {0}")]
+    EncoderSettingsError(String),
+
+    #[error("ffprobe could not read the audio stream information of {}", path.display())]
+    ProbeError { path: PathBuf },
+
+    #[error("Could not parse the ffprobe output for {}: {source}", path.display())]
+    ProbeOutputError {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("Could not read the loudness analysis of {} from the ffmpeg output", path.display())]
+    LoudnessAnalysisError { path: PathBuf },
+
+    #[error("{} does not contain any chapters", path.display())]
+    NoChaptersError { path: PathBuf },
+
+    #[error("Unknown format specifier: {0}")]
+    UnknownFormatSpecifierError(String),
+
+    #[error("Invalid file name pattern: {0}")]
+    FilenamePatternError(String),
+
+    #[error("The file name of {} does not match the pattern '{pattern}'", path.display())]
+    FilenameMismatchError { path: PathBuf, pattern: String },
+
+    #[error("Invalid rename template: {0}")]
+    RenameTemplateError(String),
+
+    #[error("{} has no {field} tag", path.display())]
+    MissingTagError { path: PathBuf, field: String },
+
+    #[error("Cannot rename to {}, it already exists or more than one file would be renamed to it", path.display())]
+    RenameCollisionError { path: PathBuf },
+
+    #[error("Could not find a disc number matching '{pattern}' in {}", path.display())]
+    DiscPatternError { path: PathBuf, pattern: String },
+
+    #[error("'{0}' is not a valid ID3v2 frame ID, expected four uppercase letters or digits")]
+    FrameIdError(String),
+
+    #[error("Invalid frame selector: {0}")]
+    FrameSelectorError(String),
+
+    #[error("Invalid frame value: {0}")]
+    FrameValueError(String),
+
+    #[error("Writing {0} frames is not supported")]
+    UnsupportedFrameError(String),
+
+    #[error("Cannot write an ID3 tag to {}, only mp3, wav and aiff files are supported", path.display())]
+    UnsupportedContainerError { path: PathBuf },
+
+    #[error("{0} of {1} files could not be processed")]
+    BatchError(usize, usize),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,509 @@
+use crate::{helper::sanitize_file_name, metadata::BookMetadata, reports::FileTags, Error, Result};
+use id3::{Tag, TagLike};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagField {
+    Author,
+    Book,
+    Track,
+    Disc,
+    Title,
+    Narrator,
+    Year,
+    Ignore,
+}
+
+impl TagField {
+    pub fn frame_id(&self) -> Option<&'static str> {
+        match self {
+            TagField::Author => Some("TPE1"),
+            TagField::Book => Some("TALB"),
+            TagField::Track => Some("TRCK"),
+            TagField::Disc => Some("TPOS"),
+            TagField::Title => Some("TIT2"),
+            TagField::Narrator => Some("TCOM"),
+            TagField::Year => Some("TYER"),
+            TagField::Ignore => None,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, TagField::Track | TagField::Disc | TagField::Year)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternPart {
+    Text(String),
+    Field(TagField),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenamePattern {
+    pattern: String,
+    parts: Vec<PatternPart>,
+}
+
+impl FilenamePattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut parts: Vec<PatternPart> = Vec::new();
+        let mut text = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let field = match chars.next() {
+                Some('%') => {
+                    text.push('%');
+                    continue;
+                }
+                Some('a') => TagField::Author,
+                Some('b') => TagField::Book,
+                Some('n') => TagField::Track,
+                Some('d') => TagField::Disc,
+                Some('t') => TagField::Title,
+                Some('c') => TagField::Narrator,
+                Some('y') => TagField::Year,
+                Some('x') => TagField::Ignore,
+                other => {
+                    return Err(Error::UnknownFormatSpecifierError(format!(
+                        "%{}",
+                        other.map(String::from).unwrap_or_default()
+                    )))
+                }
+            };
+            if text.is_empty() && matches!(parts.last(), Some(PatternPart::Field(_))) {
+                return Err(Error::FilenamePatternError(format!(
+                    "the fields in '{pattern}' must be separated by text"
+                )));
+            }
+            if !text.is_empty() {
+                parts.push(PatternPart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(PatternPart::Field(field));
+        }
+        if !text.is_empty() {
+            parts.push(PatternPart::Text(text));
+        }
+        if !parts
+            .iter()
+            .any(|part| matches!(part, PatternPart::Field(field) if field.frame_id().is_some()))
+        {
+            return Err(Error::FilenamePatternError(format!(
+                "'{pattern}' does not contain any tag fields"
+            )));
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            parts,
+        })
+    }
+
+    pub fn match_stem(&self, stem: &str) -> Option<Vec<(TagField, String)>> {
+        let mut values: Vec<(TagField, String)> = Vec::new();
+        let mut rest = stem;
+        let mut parts = self.parts.iter().peekable();
+        while let Some(part) = parts.next() {
+            match part {
+                PatternPart::Text(text) => rest = rest.strip_prefix(text.as_str())?,
+                PatternPart::Field(field) => {
+                    let end = match parts.peek() {
+                        Some(PatternPart::Text(next)) => rest.find(next.as_str())?,
+                        _ => rest.len(),
+                    };
+                    let value = rest[..end].trim();
+                    if value.is_empty() {
+                        return None;
+                    }
+                    let value = if field.is_numeric() {
+                        value.parse::<u32>().ok()?.to_string()
+                    } else {
+                        value.to_string()
+                    };
+                    values.push((*field, value));
+                    rest = &rest[end..];
+                }
+            }
+        }
+        rest.is_empty().then_some(values)
+    }
+
+    pub fn match_path(&self, path: &Path) -> Result<Vec<(TagField, String)>> {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        self.match_stem(&stem)
+            .ok_or_else(|| Error::FilenameMismatchError {
+                path: path.to_path_buf(),
+                pattern: self.pattern.clone(),
+            })
+    }
+}
+
+pub fn apply_to_file_tags(file_tags: &mut FileTags, values: &[(TagField, String)]) {
+    for (field, value) in values {
+        match field {
+            TagField::Author => file_tags.artist = Some(value.clone()),
+            TagField::Book => file_tags.album = Some(value.clone()),
+            TagField::Track => file_tags.track = value.parse().ok(),
+            TagField::Disc => file_tags.disc = value.parse().ok(),
+            TagField::Title => file_tags.title = Some(value.clone()),
+            TagField::Narrator => file_tags.composer = Some(value.clone()),
+            TagField::Year => {
+                if let Ok(year) = value.parse() {
+                    file_tags.metadata.set_year(year);
+                }
+            }
+            TagField::Ignore => {}
+        }
+    }
+}
+
+pub fn apply_to_tag(tag: &mut Tag, values: &[(TagField, String)]) {
+    for (field, value) in values {
+        match field {
+            TagField::Track => {
+                if let Ok(track) = value.parse() {
+                    tag.set_track(track);
+                }
+            }
+            TagField::Disc => {
+                if let Ok(disc) = value.parse() {
+                    tag.set_disc(disc);
+                }
+            }
+            TagField::Year => {
+                if let Ok(year) = value.parse() {
+                    tag.set_year(year);
+                }
+            }
+            field => {
+                if let Some(frame_id) = field.frame_id() {
+                    tag.set_text(frame_id, value.as_str());
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RenamePart {
+    Text(String),
+    Field { name: String, width: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameTemplate {
+    template: String,
+    parts: Vec<RenamePart>,
+}
+
+pub const RENAME_FIELDS: [&str; 10] = [
+    "author",
+    "album_artist",
+    "narrator",
+    "title",
+    "chapter",
+    "track",
+    "disc",
+    "year",
+    "series",
+    "series_index",
+];
+
+impl RenameTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts: Vec<RenamePart> = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(RenamePart::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                Error::RenameTemplateError(format!("unclosed '{{' in '{template}'"))
+            })? + start;
+            let field = &rest[start + 1..end];
+            let (name, width) = match field.split_once(':') {
+                Some((name, width)) => (
+                    name,
+                    width.parse().map_err(|_| {
+                        Error::RenameTemplateError(format!("invalid width in '{{{field}}}'"))
+                    })?,
+                ),
+                None => (field, 0),
+            };
+            if !RENAME_FIELDS.contains(&name) {
+                return Err(Error::RenameTemplateError(format!(
+                    "unknown field '{name}', expected one of: {}",
+                    RENAME_FIELDS.join(", ")
+                )));
+            }
+            parts.push(RenamePart::Field {
+                name: name.to_string(),
+                width,
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(RenamePart::Text(rest.to_string()));
+        }
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn render(&self, source: &Path, tag: &Tag) -> Result<PathBuf> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                RenamePart::Text(text) => rendered.push_str(text),
+                RenamePart::Field { name, width } => {
+                    let value = tag_field(tag, name).ok_or_else(|| Error::MissingTagError {
+                        path: source.to_path_buf(),
+                        field: name.clone(),
+                    })?;
+                    let value = match value.parse::<u32>() {
+                        Ok(number) if *width > 0 => format!("{number:0width$}"),
+                        _ => value,
+                    };
+                    rendered.push_str(&sanitize_file_name(&value));
+                }
+            }
+        }
+
+        let mut path = PathBuf::new();
+        for component in rendered.split(['/', '\\']) {
+            let component = component.trim().trim_end_matches('.');
+            if component.is_empty() || component == "." {
+                return Err(Error::RenameTemplateError(format!(
+                    "'{}' renders an empty folder or file name for {}",
+                    self.template,
+                    source.display()
+                )));
+            }
+            path.push(component);
+        }
+        if let Some(extension) = source.extension() {
+            if !self.ends_with_extension(&extension.to_string_lossy()) {
+                let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+                file_name.push(".");
+                file_name.push(extension);
+                path.set_file_name(file_name);
+            }
+        }
+        Ok(path)
+    }
+
+    // Tag values may contain dots ("Ch. 1"), so only literal text at the end
+    // of the template counts as an extension.
+    fn ends_with_extension(&self, extension: &str) -> bool {
+        match self.parts.last() {
+            Some(RenamePart::Text(text)) => text
+                .to_lowercase()
+                .ends_with(&format!(".{}", extension.to_lowercase())),
+            _ => false,
+        }
+    }
+}
+
+pub fn tag_field(tag: &Tag, name: &str) -> Option<String> {
+    let text = |frame_id: &str| {
+        tag.get(frame_id)
+            .and_then(|frame| frame.content().text())
+            .map(str::to_string)
+    };
+    let value = match name {
+        "author" => tag.artist().map(str::to_string),
+        "album_artist" => tag.album_artist().map(str::to_string),
+        "narrator" => text("TCOM"),
+        "title" => tag.album().map(str::to_string),
+        "chapter" => tag.title().map(str::to_string),
+        "track" => tag.track().map(|track| track.to_string()),
+        "disc" => tag.disc().map(|disc| disc.to_string()),
+        "year" => tag.year().map(|year| year.to_string()),
+        "series" => BookMetadata::from_tag(tag).series(),
+        "series_index" => BookMetadata::from_tag(tag).series_index(),
+        _ => None,
+    };
+    value.filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag() -> Tag {
+        let mut tag = Tag::new();
+        tag.set_artist("Jane Doe");
+        tag.set_album("The Book");
+        tag.set_title("Ch. 1");
+        tag.set_track(3);
+        tag
+    }
+
+    fn render(template: &str, source: &str) -> Result<PathBuf> {
+        RenameTemplate::parse(template)?.render(Path::new(source), &tag())
+    }
+
+    fn match_stem(pattern: &str, stem: &str) -> Option<Vec<(TagField, String)>> {
+        FilenamePattern::parse(pattern).unwrap().match_stem(stem)
+    }
+
+    #[test]
+    fn pattern_matches_request_example() {
+        assert_eq!(
+            match_stem("%a - %b - %n - %t", "Jane Doe - The Book - 03 - Intro"),
+            Some(vec![
+                (TagField::Author, "Jane Doe".to_string()),
+                (TagField::Book, "The Book".to_string()),
+                (TagField::Track, "3".to_string()),
+                (TagField::Title, "Intro".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn pattern_splits_on_first_separator() {
+        assert_eq!(
+            match_stem("%n - %t", "1 - Part One - The Start"),
+            Some(vec![
+                (TagField::Track, "1".to_string()),
+                (TagField::Title, "Part One - The Start".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn pattern_requires_numbers_in_numeric_fields() {
+        assert_eq!(
+            match_stem("%d-%n %t", "02-007 Intro"),
+            Some(vec![
+                (TagField::Disc, "2".to_string()),
+                (TagField::Track, "7".to_string()),
+                (TagField::Title, "Intro".to_string()),
+            ])
+        );
+        assert_eq!(match_stem("%n %t", "One Intro"), None);
+    }
+
+    #[test]
+    fn pattern_rejects_adjacent_fields() {
+        assert!(matches!(
+            FilenamePattern::parse("%n%t"),
+            Err(Error::FilenamePatternError(_))
+        ));
+        assert!(matches!(
+            FilenamePattern::parse("%n %q"),
+            Err(Error::UnknownFormatSpecifierError(_))
+        ));
+    }
+
+    #[test]
+    fn pattern_treats_double_percent_as_text() {
+        assert_eq!(
+            match_stem("%n%% %t", "50% Halfway"),
+            Some(vec![
+                (TagField::Track, "50".to_string()),
+                (TagField::Title, "Halfway".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn pattern_matches_trailing_text() {
+        assert_eq!(
+            match_stem("%n - %t (abridged)", "4 - End (abridged)"),
+            Some(vec![
+                (TagField::Track, "4".to_string()),
+                (TagField::Title, "End".to_string()),
+            ])
+        );
+        assert_eq!(match_stem("%n - %t (abridged)", "4 - End"), None);
+    }
+
+    #[test]
+    fn pattern_mismatch_names_file_and_pattern() {
+        let pattern = FilenamePattern::parse("%a - %b - %n - %t").unwrap();
+        assert!(matches!(
+            pattern.match_path(Path::new("book/cover notes.mp3")),
+            Err(Error::FilenameMismatchError { path, pattern })
+                if path == Path::new("book/cover notes.mp3") && pattern == "%a - %b - %n - %t"
+        ));
+    }
+
+    #[test]
+    fn parse_splits_text_and_fields() {
+        let template = RenameTemplate::parse("{author}/{track:02} - {chapter}").unwrap();
+        assert_eq!(
+            template.parts,
+            [
+                RenamePart::Field {
+                    name: "author".to_string(),
+                    width: 0
+                },
+                RenamePart::Text("/".to_string()),
+                RenamePart::Field {
+                    name: "track".to_string(),
+                    width: 2
+                },
+                RenamePart::Text(" - ".to_string()),
+                RenamePart::Field {
+                    name: "chapter".to_string(),
+                    width: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_fields_and_widths() {
+        assert!(RenameTemplate::parse("{publisher}").is_err());
+        assert!(RenameTemplate::parse("{track:x}").is_err());
+        assert!(RenameTemplate::parse("{track").is_err());
+    }
+
+    #[test]
+    fn render_pads_numbers_to_width() {
+        assert_eq!(
+            render("{track:03} {title}", "a.mp3").unwrap(),
+            PathBuf::from("003 The Book.mp3")
+        );
+    }
+
+    #[test]
+    fn render_fails_on_missing_field() {
+        assert!(matches!(
+            render("{narrator}", "a.mp3"),
+            Err(Error::MissingTagError { field, .. }) if field == "narrator"
+        ));
+    }
+
+    #[test]
+    fn render_sanitises_values() {
+        let mut tag = tag();
+        tag.set_album("Why? A/B: Story");
+        let path = RenameTemplate::parse("{author}/{title}")
+            .unwrap()
+            .render(Path::new("a.mp3"), &tag)
+            .unwrap();
+        assert_eq!(path, PathBuf::from("Jane Doe/Why_ A_B_ Story.mp3"));
+    }
+
+    #[test]
+    fn render_appends_extension_after_dotted_value() {
+        assert_eq!(
+            render("{track} {chapter}", "a.mp3").unwrap(),
+            PathBuf::from("3 Ch. 1.mp3")
+        );
+    }
+
+    #[test]
+    fn render_keeps_literal_extension() {
+        assert_eq!(
+            render("{chapter}.MP3", "a.mp3").unwrap(),
+            PathBuf::from("Ch. 1.MP3")
+        );
+    }
+}
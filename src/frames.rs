@@ -0,0 +1,237 @@
+use crate::{Error, Result};
+use id3::{
+    frame::{Comment, ExtendedLink, ExtendedText, Lyrics, Popularimeter, Unknown},
+    Content, Frame, Tag, TagLike, Version,
+};
+
+pub const DESCRIBED_FRAMES: [&str; 10] = [
+    "TXXX", "WXXX", "COMM", "USLT", "SYLT", "APIC", "GEOB", "POPM", "PRIV", "UFID",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSelector {
+    id: String,
+    description: Option<String>,
+    language: Option<String>,
+}
+
+impl FrameSelector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let (id, description) = match selector.split_once(':') {
+            Some((id, description)) => (id, Some(description.to_string())),
+            None => (selector, None),
+        };
+        if !is_frame_id(id) {
+            return Err(Error::FrameIdError(id.to_string()));
+        }
+        if description.is_some() && !DESCRIBED_FRAMES.contains(&id) {
+            return Err(Error::FrameSelectorError(format!(
+                "{id} frames have no description, only {} do",
+                DESCRIBED_FRAMES.join(", ")
+            )));
+        }
+        Ok(Self {
+            id: id.to_string(),
+            description,
+            language: None,
+        })
+    }
+
+    pub fn matches(&self, frame: &Frame) -> bool {
+        if frame.id() != self.id {
+            return false;
+        }
+        let same_description = match &self.description {
+            Some(description) => frame_description(frame).is_some_and(|frame_description| {
+                frame_description.eq_ignore_ascii_case(description)
+            }),
+            None => true,
+        };
+        let same_language = match &self.language {
+            Some(language) => frame_language(frame)
+                .is_some_and(|frame_language| frame_language.eq_ignore_ascii_case(language)),
+            None => true,
+        };
+        same_description && same_language
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameEdit {
+    selector: FrameSelector,
+    frame: Frame,
+}
+
+impl FrameEdit {
+    pub fn parse(frame: &str, value: &str, language: Option<&str>) -> Result<Self> {
+        let mut selector = FrameSelector::parse(frame)?;
+        let id: String = selector.id.clone();
+        let description = selector.description.clone().unwrap_or_default();
+        let lang = language.unwrap_or("eng").to_string();
+        if language.is_some() && !matches!(id.as_str(), "COMM" | "USLT") {
+            return Err(Error::FrameValueError(format!(
+                "{id} frames have no language, only COMM and USLT do"
+            )));
+        }
+        if lang.len() != 3 || !lang.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(Error::FrameValueError(format!(
+                "'{lang}' is not a three letter ISO 639-2 language code"
+            )));
+        }
+
+        if matches!(id.as_str(), "COMM" | "USLT") {
+            selector.language = Some(lang.clone());
+        }
+
+        let content = match id.as_str() {
+            "TXXX" => Content::ExtendedText(ExtendedText {
+                description,
+                value: value.to_string(),
+            }),
+            "WXXX" => Content::ExtendedLink(ExtendedLink {
+                description,
+                link: value.to_string(),
+            }),
+            "COMM" => Content::Comment(Comment {
+                lang,
+                description,
+                text: value.to_string(),
+            }),
+            "USLT" => Content::Lyrics(Lyrics {
+                lang,
+                description,
+                text: value.to_string(),
+            }),
+            "POPM" => Content::Popularimeter(Popularimeter {
+                user: description,
+                rating: value.parse().map_err(|_| {
+                    Error::FrameValueError(format!(
+                        "the POPM rating must be a number from 0 to 255, not '{value}'"
+                    ))
+                })?,
+                counter: 0,
+            }),
+            "MVNM" | "MVIN" => movement_frame(&id, value).content().clone(),
+            "TIPL" | "TMCL" => return Err(Error::UnsupportedFrameError(id)),
+            "GRP1" => Content::Text(value.to_string()),
+            _ if id.starts_with('T') => Content::Text(value.to_string()),
+            _ if id.starts_with('W') => Content::Link(value.to_string()),
+            _ => return Err(Error::UnsupportedFrameError(id)),
+        };
+        if DESCRIBED_FRAMES.contains(&id.as_str()) {
+            selector.description.get_or_insert_with(String::new);
+        }
+
+        Ok(Self {
+            selector,
+            frame: Frame::with_content(&id, content),
+        })
+    }
+
+    pub fn apply(&self, tag: &mut Tag) {
+        let others: Vec<Frame> = tag
+            .remove(&self.selector.id)
+            .into_iter()
+            .filter(|frame| !self.selector.matches(frame))
+            .collect();
+        for frame in others {
+            tag.add_frame(frame);
+        }
+        tag.add_frame(self.frame.clone());
+    }
+}
+
+pub fn parse_selectors(
+    selectors: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<Vec<FrameSelector>> {
+    selectors
+        .into_iter()
+        .map(|selector| FrameSelector::parse(selector.as_ref()))
+        .collect()
+}
+
+pub fn is_frame_id(id: &str) -> bool {
+    id.len() == 4
+        && id
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+fn frame_language(frame: &Frame) -> Option<&str> {
+    match frame.content() {
+        Content::Comment(comment) => Some(&comment.lang),
+        Content::Lyrics(lyrics) => Some(&lyrics.lang),
+        Content::SynchronisedLyrics(lyrics) => Some(&lyrics.lang),
+        _ => None,
+    }
+}
+
+fn frame_description(frame: &Frame) -> Option<&str> {
+    let description = match frame.content() {
+        Content::ExtendedText(extended_text) => &extended_text.description,
+        Content::ExtendedLink(extended_link) => &extended_link.description,
+        Content::Comment(comment) => &comment.description,
+        Content::Lyrics(lyrics) => &lyrics.description,
+        Content::SynchronisedLyrics(lyrics) => &lyrics.description,
+        Content::Picture(picture) => &picture.description,
+        Content::EncapsulatedObject(object) => &object.description,
+        Content::Popularimeter(popularimeter) => &popularimeter.user,
+        Content::Private(private) => &private.owner_identifier,
+        Content::UniqueFileIdentifier(identifier) => &identifier.owner_identifier,
+        _ => return None,
+    };
+    Some(description)
+}
+
+// The id3 crate only knows MVNM and MVIN as unknown frames, so they are encoded
+// and decoded here like any other text frame.
+pub fn movement_text(tag: &Tag, frame_id: &str) -> Option<String> {
+    let data = match tag.get(frame_id)?.content() {
+        Content::Text(text) => return Some(text.clone()),
+        Content::Unknown(unknown) => &unknown.data,
+        _ => return None,
+    };
+    let (encoding, bytes) = data.split_first()?;
+    let utf16 = |bytes: &[u8], big_endian: bool| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| match big_endian {
+                true => u16::from_be_bytes([pair[0], pair[1]]),
+                false => u16::from_le_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    let text = match encoding {
+        0 => bytes.iter().map(|byte| char::from(*byte)).collect(),
+        1 => match bytes {
+            [0xfe, 0xff, rest @ ..] => utf16(rest, true),
+            [0xff, 0xfe, rest @ ..] => utf16(rest, false),
+            _ => utf16(bytes, false),
+        },
+        2 => utf16(bytes, true),
+        3 => String::from_utf8_lossy(bytes).into_owned(),
+        _ => return None,
+    };
+    Some(text.trim_end_matches('\0').to_string())
+}
+
+pub fn movement_frame(frame_id: &str, text: &str) -> Frame {
+    let data: Vec<u8> = if text.chars().all(|c| (c as u32) < 0x100) {
+        std::iter::once(0)
+            .chain(text.chars().map(|c| c as u8))
+            .collect()
+    } else {
+        [1, 0xff, 0xfe]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect()
+    };
+    Frame::with_content(
+        frame_id,
+        Content::Unknown(Unknown {
+            data,
+            version: Version::Id3v23,
+        }),
+    )
+}
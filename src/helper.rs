@@ -1,7 +1,16 @@
-use crate::{Error, Result};
-use clap::parser::ValuesRef;
+use crate::{options::PathFilter, progress::Event, Error, Result};
 use id3::{Content, Frame, Tag, TagLike, Version};
-use std::{collections::BTreeSet, io, path::{Path, PathBuf}, process::Command};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Read},
+    iter::Peekable,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::Chars,
+    thread,
+};
 
 // pub fn generate_metadata(
 //     paths: &BTreeSet<PathBuf>,
@@ -38,17 +47,56 @@ use std::{collections::BTreeSet, io, path::{Path, PathBuf}, process::Command};
 //     Ok(ffmetadata)
 // }
 
-pub fn expand_wildcards(raw_paths: ValuesRef<String>) -> Result<BTreeSet<PathBuf>> {
-    let mut parsed_paths: BTreeSet<PathBuf> = BTreeSet::new();
+pub const AUDIO_EXTENSIONS: [&str; 12] = [
+    "mp3", "m4a", "m4b", "aac", "opus", "ogg", "oga", "flac", "wav", "aif", "aiff", "wma",
+];
+
+// Containers the id3 crate can write a tag to without corrupting the file.
+pub const ID3_EXTENSIONS: [&str; 4] = ["mp3", "wav", "aif", "aiff"];
+
+pub fn expand_wildcards(
+    raw_paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    extensions: &[&str],
+) -> Result<Vec<PathBuf>> {
+    collect_paths(raw_paths, &PathFilter::new().extensions(extensions))
+}
+
+pub fn collect_paths(
+    raw_paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    filter: &PathFilter,
+) -> Result<Vec<PathBuf>> {
+    let mut parsed_paths: Vec<PathBuf> = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let mut visited_dirs: HashSet<PathBuf> = HashSet::new();
 
     for raw_path in raw_paths {
-        match glob::glob(&raw_path) {
-            Ok(globs) => {
-                for glob_path in globs {
-                    parsed_paths.insert(glob_path.unwrap().canonicalize()?);
-                }
+        let raw_path = raw_path.as_ref();
+        let matches: Vec<PathBuf> = if raw_path.exists() {
+            vec![raw_path.to_path_buf()]
+        } else {
+            match glob::glob(&raw_path.to_string_lossy()) {
+                Ok(globs) => globs.collect::<std::result::Result<_, _>>()?,
+                Err(glob_error) => return Err(Error::GlobError(glob_error)),
+            }
+        };
+        let mut files: Vec<PathBuf> = Vec::new();
+        for path in matches {
+            if path.is_dir() {
+                walk_directory(&path, filter, &mut files, &mut visited_dirs)?;
+            } else {
+                files.push(path);
+            }
+        }
+        for path in files {
+            if !filter.matches(&path) {
+                continue;
+            }
+            let path = path
+                .canonicalize()
+                .map_err(|source| Error::FileError { path, source })?;
+            if seen.insert(path.clone()) {
+                parsed_paths.push(path);
             }
-            Err(glob_error) => return Err(Error::GlobError(glob_error)),
         }
     }
     if parsed_paths.is_empty() {
@@ -57,43 +105,390 @@ pub fn expand_wildcards(raw_paths: ValuesRef<String>) -> Result<BTreeSet<PathBuf
     Ok(parsed_paths)
 }
 
-pub fn write_tag(path: &PathBuf, frame_id: &str, new_text: &str) -> Result<()> {
+fn walk_directory(
+    dir: &Path,
+    filter: &PathFilter,
+    files: &mut Vec<PathBuf>,
+    visited_dirs: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    let file_error = |source| Error::FileError {
+        path: dir.to_path_buf(),
+        source,
+    };
+    if !visited_dirs.insert(dir.canonicalize().map_err(file_error)?) {
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .and_then(|entries| entries.map(|entry| Ok(entry?.path())).collect())
+        .map_err(file_error)?;
+    entries.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b)));
+
+    for entry in entries {
+        if file_name(&entry).starts_with('.') {
+            continue;
+        }
+        let is_symlink = entry
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+        if is_symlink && entry.is_dir() && !filter.follow_symlinks {
+            continue;
+        }
+        if entry.is_dir() {
+            walk_directory(&entry, filter, files, visited_dirs)?;
+        } else if has_extension(&entry, &filter.directory_extensions()) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+pub fn has_extension(path: &Path, extensions: &[impl AsRef<str>]) -> bool {
+    path.extension().is_some_and(|extension| {
+        extensions
+            .iter()
+            .any(|candidate| extension.eq_ignore_ascii_case(candidate.as_ref()))
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                x.len().cmp(&y.len()).then(x.cmp(&y))
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                a.next();
+                b.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        number.push(digit);
+    }
+    number.trim_start_matches('0').to_string()
+}
+
+pub fn parse_path_list(list: &str) -> Vec<PathBuf> {
+    let separator = if list.contains('\0') { '\0' } else { '\n' };
+    list.split(separator)
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+pub fn read_stdin_paths() -> Result<Vec<PathBuf>> {
+    let mut list: Vec<u8> = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut list)
+        .map_err(|_| Error::StdinError)?;
+    literal_paths(&String::from_utf8_lossy(&list))
+}
+
+pub fn read_path_file(path: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    match fs::read(&path) {
+        Ok(list) => literal_paths(&String::from_utf8_lossy(&list)),
+        Err(source) => Err(Error::FileError {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
+    }
+}
+
+fn literal_paths(list: &str) -> Result<Vec<PathBuf>> {
+    parse_path_list(list)
+        .into_iter()
+        .map(|path| {
+            path.canonicalize()
+                .map_err(|source| Error::FileError { path, source })
+        })
+        .collect()
+}
+
+pub fn write_tag(path: impl AsRef<Path>, frame_id: &str, new_text: &str) -> Result<()> {
     let mut tag: Tag = read_tag(&path)?;
     let frame = Frame::with_content(frame_id, Content::Text(new_text.to_string()));
     tag.add_frame(frame);
-    if let Err(err) = tag.write_to_path(path, Version::Id3v23) {
-        return Err(Error::Id3Error(err));
+    save_tag(path, &tag)
+}
+
+pub fn save_tag(path: impl AsRef<Path>, tag: &Tag) -> Result<()> {
+    match tag.write_to_path(&path, Version::Id3v23) {
+        Ok(()) => Ok(()),
+        Err(source) => Err(Error::Id3Error {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
+    }
+}
+
+pub fn remove_id3v2(path: impl AsRef<Path>) -> Result<()> {
+    match Tag::remove_from_path(&path) {
+        Ok(_) => Ok(()),
+        Err(source) => Err(Error::Id3Error {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
+    }
+}
+
+pub fn remove_id3v1(path: impl AsRef<Path>) -> Result<()> {
+    match id3::v1::Tag::remove_from_path(&path) {
+        Ok(_) => Ok(()),
+        Err(source) => Err(Error::Id3Error {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
+    }
+}
+
+pub fn check_editable(path: impl AsRef<Path>) -> Result<()> {
+    if !has_extension(path.as_ref(), &ID3_EXTENSIONS) {
+        return Err(Error::UnsupportedContainerError {
+            path: path.as_ref().to_path_buf(),
+        });
+    }
+    read_tag(&path)?;
+    check_writable(path)
+}
+
+pub fn check_writable(path: impl AsRef<Path>) -> Result<()> {
+    match OpenOptions::new().write(true).open(&path) {
+        Ok(_) => Ok(()),
+        Err(source) => Err(Error::FileError {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
     }
-    Ok(())
 }
 
 pub fn read_tag(path: impl AsRef<Path>) -> Result<Tag> {
-    match Tag::read_from_path(path) {
+    match Tag::read_from_path(&path) {
         Ok(tag) => Ok(tag),
         Err(id3::Error {
             kind: id3::ErrorKind::NoTag,
             ..
         }) => Ok(Tag::new()),
-        Err(err) => {
-            return Err(Error::Id3Error(err));
-        }
+        Err(source) => Err(Error::Id3Error {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }),
     }
 }
 
-pub fn run_ffmpeg<'a>(ffmpeg_path: &str, arguments: impl IntoIterator<Item = &'a str>) -> Result<()> {
-    let status = match Command::new(ffmpeg_path).args(arguments).status() {
-        Ok(status) => status,
+pub fn run_ffmpeg<'a>(
+    ffmpeg_path: &str,
+    arguments: impl IntoIterator<Item = &'a str>,
+    inputs: &[&Path],
+    total_duration: Option<u32>,
+    report: &dyn Fn(Event),
+    overwrite: bool,
+) -> Result<()> {
+    let overwrite_flag = if overwrite { "-y" } else { "-n" };
+    let arguments: Vec<&str> = [
+        "-hide_banner",
+        "-nostats",
+        overwrite_flag,
+        "-progress",
+        "pipe:1",
+    ]
+    .into_iter()
+    .chain(arguments)
+    .collect();
+    let mut child = match Command::new(ffmpeg_path)
+        .args(&arguments)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
         Err(err) if err.kind() == io::ErrorKind::NotFound => {
             return Err(Error::FfmpegNotFoundError(ffmpeg_path.to_string()))
         }
         Err(err) => return Err(Error::IoError(err)),
     };
+
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| io::Error::other("ffmpeg stderr was not captured"))?;
+    let stderr_reader = thread::spawn(move || {
+        let mut output = String::new();
+        stderr.read_to_string(&mut output).ok();
+        output
+    });
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| io::Error::other("ffmpeg stdout was not captured"))?;
+    let mut position: u32 = 0;
+    let mut speed: Option<f64> = None;
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        match line.split_once('=') {
+            Some(("out_time_us", value)) => {
+                position = value
+                    .parse::<u64>()
+                    .map_or(position, |us| (us / 1000) as u32);
+            }
+            Some(("speed", value)) => {
+                speed = value.trim().trim_end_matches('x').parse().ok();
+            }
+            Some(("progress", _)) => {
+                if let Some(total) = total_duration {
+                    report(Event::Progress {
+                        position,
+                        total,
+                        speed,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let status = child.wait()?;
+    let stderr_output = stderr_reader.join().unwrap_or_default();
+    if total_duration.is_some() {
+        report(Event::ProgressEnd);
+    }
     match status.code() {
-        Some(0) => {
-            println!("Finished");
-            Ok(())
+        Some(0) => Ok(()),
+        code => Err(ffmpeg_error(
+            ffmpeg_path,
+            &arguments,
+            inputs,
+            code.unwrap_or(1),
+            &stderr_output,
+        )),
+    }
+}
+
+pub fn run_ffmpeg_analysis<'a>(
+    ffmpeg_path: &str,
+    arguments: impl IntoIterator<Item = &'a str>,
+    input: &Path,
+) -> Result<String> {
+    let arguments: Vec<&str> = arguments.into_iter().collect();
+    let output = match Command::new(ffmpeg_path).args(&arguments).output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::FfmpegNotFoundError(ffmpeg_path.to_string()))
         }
-        Some(code) => Err(Error::FfmpegError(code)),
-        None => Err(Error::FfmpegError(1)),
+        Err(err) => return Err(Error::IoError(err)),
+    };
+    let stderr_output = String::from_utf8_lossy(&output.stderr).into_owned();
+    match output.status.code() {
+        Some(0) => Ok(stderr_output),
+        code => Err(ffmpeg_error(
+            ffmpeg_path,
+            &arguments,
+            &[input],
+            code.unwrap_or(1),
+            &stderr_output,
+        )),
+    }
+}
+
+const STDERR_TAIL_LINES: usize = 20;
+
+fn ffmpeg_error(
+    ffmpeg_path: &str,
+    arguments: &[&str],
+    inputs: &[&Path],
+    code: i32,
+    stderr: &str,
+) -> Error {
+    let command = std::iter::once(ffmpeg_path)
+        .chain(arguments.iter().copied())
+        .map(quote_argument)
+        .collect::<Vec<String>>()
+        .join(" ");
+    let lines: Vec<&str> = stderr.lines().collect();
+    let stderr_tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+
+    Error::FfmpegError {
+        code,
+        command,
+        input: if inputs.is_empty() {
+            "no input".to_string()
+        } else {
+            inputs
+                .iter()
+                .map(|input| input.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        },
+        stderr_tail,
+    }
+}
+
+fn quote_argument(argument: &str) -> String {
+    let is_plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+%@".contains(c));
+    if is_plain {
+        argument.to_string()
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+pub fn concat_list(paths: impl IntoIterator<Item = impl AsRef<Path>>) -> String {
+    paths
+        .into_iter()
+        .map(|path| {
+            format!(
+                "file '{}'",
+                path.as_ref().to_string_lossy().replace('\'', "'\\''")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn ffmpeg_timestamp(milliseconds: u32) -> String {
+    format!("{}.{:03}", milliseconds / 1000, milliseconds % 1000)
+}
+
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim().trim_end_matches('.').to_string();
+    if sanitized.is_empty() {
+        return "_".to_string();
     }
+    sanitized
 }
@@ -1,9 +1,23 @@
+pub mod backend;
 pub mod commands;
 pub mod chapters;
+pub mod encoder;
+pub mod filename;
+pub mod frames;
 pub mod helper;
+pub mod loudness;
+pub mod metadata;
+pub mod numbering;
+pub mod options;
+pub mod processing;
+pub mod progress;
+pub mod reports;
+pub mod tools;
 pub mod errors;
 
 pub use crate::commands::*;
 pub use crate::errors::*;
+pub use crate::options::*;
+pub use crate::reports::*;
 
 use helper::*;
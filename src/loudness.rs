@@ -0,0 +1,100 @@
+use crate::{backend::MediaBackend, Result};
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessTarget {
+    integrated: f64,
+    true_peak: f64,
+    loudness_range: f64,
+}
+
+impl LoudnessTarget {
+    pub fn new(integrated: f64, true_peak: f64) -> Self {
+        Self {
+            integrated,
+            true_peak,
+            loudness_range: 11.0,
+        }
+    }
+
+    pub fn integrated(&self) -> f64 {
+        self.integrated
+    }
+
+    pub fn true_peak(&self) -> f64 {
+        self.true_peak
+    }
+
+    pub fn loudness_range(&self) -> f64 {
+        self.loudness_range
+    }
+
+    pub fn set_loudness_range(&mut self, loudness_range: f64) {
+        self.loudness_range = loudness_range;
+    }
+
+    pub fn analysis_filter(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+            self.integrated, self.true_peak, self.loudness_range
+        )
+    }
+
+    pub fn normalization_filter(&self, measurement: &LoudnessMeasurement) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:\
+            measured_thresh={}:offset={}:linear=true",
+            self.integrated,
+            self.true_peak,
+            self.loudness_range,
+            measurement.integrated,
+            measurement.true_peak,
+            measurement.loudness_range,
+            measurement.threshold,
+            measurement.target_offset
+        )
+    }
+}
+
+impl Default for LoudnessTarget {
+    fn default() -> Self {
+        LoudnessTarget::new(-18.0, -1.5)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub loudness_range: f64,
+    pub threshold: f64,
+    pub target_offset: f64,
+}
+
+impl LoudnessMeasurement {
+    pub fn measure(
+        path: impl AsRef<Path>,
+        target: &LoudnessTarget,
+        backend: &dyn MediaBackend,
+    ) -> Result<Self> {
+        backend.measure_loudness(path.as_ref(), target)
+    }
+
+    pub(crate) fn from_loudnorm_output(output: &str) -> Option<Self> {
+        let start = output.rfind('{')?;
+        let end = output.rfind('}')?;
+        let json: Value = serde_json::from_str(output.get(start..=end)?).ok()?;
+        let field = |name: &str| -> Option<f64> {
+            json[name].as_str().and_then(|value| value.parse().ok())
+        };
+
+        Some(Self {
+            integrated: field("input_i")?,
+            true_peak: field("input_tp")?,
+            loudness_range: field("input_lra")?,
+            threshold: field("input_thresh")?,
+            target_offset: field("target_offset")?,
+        })
+    }
+}
@@ -1,90 +1,589 @@
 use audiobook_tagger::{
-    change_author, change_narrator, change_tag, change_title, chapters_to_toml, combine_files, example_toml, number_chapters, number_files, show_chapters, show_tags, toml_to_chapters
+    analyze_loudness, apply_renames,
+    backend::FfmpegBackend,
+    change_author, change_book_metadata, change_frame, change_narrator, change_title,
+    chapters_to_toml, clear_tags, combine_files, doctor,
+    encoder::{Codec, EncoderSettings, PRESETS},
+    example_toml,
+    frames::FrameEdit,
+    helper::{collect_paths, read_path_file, read_stdin_paths, AUDIO_EXTENSIONS, ID3_EXTENSIONS},
+    loudness::LoudnessTarget,
+    metadata::BookMetadata,
+    number_chapters, number_files,
+    numbering::DiscNumbering,
+    plan_renames, preview_tags_from_filename,
+    processing::SilenceSettings,
+    progress::{Event, ProgressBar},
+    remove_tag, show_chapters, show_tags, split_file, split_parts, strip_v1, tags_from_filename,
+    toml_to_chapters,
+    tools::ToolLocator,
+    BatchMode, BatchReport, CombineOptions, NumberingOptions, PartOptions, PathFilter,
+    SplitOptions,
+};
+use clap::{command, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
 };
-use clap::{command, parser::ValuesRef, value_parser, Arg, ArgMatches, Command};
 
 fn main() -> anyhow::Result<()> {
     let matches: ArgMatches = cli();
 
     if let Some((subcommand, args)) = matches.subcommand() {
         match subcommand {
-            "show-tags" => show_tags(args.get_many::<String>("paths").unwrap())?,
+            "show-tags" => {
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                println!("{}", show_tags(paths)?);
+            }
             "number-files" => {
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                let start: &u32 = args.get_one::<u32>("start").unwrap();
-                number_files(paths, *start)?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                let options: NumberingOptions =
+                    numbering_options(args).totals(args.get_flag("with-total"));
+                finish_batch(args, number_files(paths, &options, batch_mode(args))?)?;
             }
             "number-file-titles" => {
                 let naming_scheme: &String = args.get_one("naming-scheme").unwrap();
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                let start: &i32 = args.get_one("start").unwrap();
-                number_chapters(naming_scheme, paths, *start)?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                let options: NumberingOptions = numbering_options(args);
+                finish_batch(
+                    args,
+                    number_chapters(naming_scheme, paths, &options, batch_mode(args))?,
+                )?;
+            }
+            "tags-from-filename" => {
+                let pattern: &String = args.get_one("pattern").unwrap();
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                if args.get_flag("dry-run") {
+                    println!("{}", preview_tags_from_filename(pattern, paths)?);
+                } else {
+                    finish_batch(args, tags_from_filename(pattern, paths, batch_mode(args))?)?;
+                }
+            }
+            "rename" => {
+                let template: &String = args.get_one("template").unwrap();
+                let output_dir: &String = args.get_one("output-dir").unwrap();
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                let plan = plan_renames(template, paths, output_dir)?;
+                if args.get_flag("dry-run") {
+                    println!("{}", plan);
+                } else {
+                    apply_renames(&plan)?;
+                }
             }
             "change-title" => {
                 let title: &String = args.get_one("title").unwrap();
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                change_title(title, paths)?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, change_title(title, paths, batch_mode(args))?)?;
             }
             "change-author" => {
                 let author: &String = args.get_one("author").unwrap();
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                change_author(author, paths)?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, change_author(author, paths, batch_mode(args))?)?;
             }
             "change-narrator" => {
                 let narrator: &String = args.get_one("narrator").unwrap();
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                change_narrator(narrator, paths)?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, change_narrator(narrator, paths, batch_mode(args))?)?;
+            }
+            "change-book-info" => {
+                let metadata: BookMetadata = book_metadata(args);
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(
+                    args,
+                    change_book_metadata(&metadata, paths, batch_mode(args))?,
+                )?;
             }
             "change-tag" => {
-                let frame_id: &String = args.get_one("tag").unwrap();
+                let frame: &String = args.get_one("tag").unwrap();
                 let new_text: &String = args.get_one("value").unwrap();
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
-                change_tag(frame_id, new_text, paths)?;
+                let language: Option<&String> = args.get_one("language");
+                let edit = FrameEdit::parse(frame, new_text, language.map(String::as_str))?;
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, change_frame(&edit, paths, batch_mode(args))?)?;
+            }
+            "remove-tag" => {
+                let frames = args.get_many::<String>("frames").unwrap();
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, remove_tag(frames, paths, batch_mode(args))?)?;
+            }
+            "clear-tags" => {
+                let keep = args.get_many::<String>("keep").into_iter().flatten();
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, clear_tags(keep, paths, batch_mode(args))?)?;
+            }
+            "strip-v1" => {
+                let paths: Vec<PathBuf> = input_paths(args, &ID3_EXTENSIONS)?;
+                finish_batch(args, strip_v1(paths, batch_mode(args))?)?;
             }
             "combine-files" => {
-                let paths: ValuesRef<String> = args.get_many("paths").unwrap();
+                let paths: Vec<PathBuf> = input_paths(args, &AUDIO_EXTENSIONS)?;
                 let output: &String = args.get_one("output").unwrap();
-                let bitrate: &u32 = args.get_one("bitrate").unwrap();
-                let title: &String = args.get_one("title").unwrap();
-                let author: &String = args.get_one("author").unwrap();
-                let ffmpeg_path: &String = args.get_one("ffmpeg-path").unwrap();
-                combine_files(paths, output, *bitrate, title, author, ffmpeg_path)?;
+                let options: CombineOptions = combine_options(args)?;
+                let required_encoders: Vec<&str> = match options.encoder_settings().codec() {
+                    Codec::Copy => vec![],
+                    codec => vec![codec.encoder()],
+                };
+                let backend: FfmpegBackend = ffmpeg_backend(args, &required_encoders)?;
+                let report = combine_files(paths, output, &options, &backend)?;
+                for conflict in &report.conflicts {
+                    eprintln!("Warning: {conflict}");
+                }
+                println!(
+                    "Wrote {} with {} chapters",
+                    report.output.to_string_lossy(),
+                    report.chapters.len()
+                );
+            }
+            "analyze-loudness" => {
+                let paths: Vec<PathBuf> = input_paths(args, &AUDIO_EXTENSIONS)?;
+                let target: LoudnessTarget = loudness_target(args);
+                let backend: FfmpegBackend = ffmpeg_backend(args, &[])?;
+                println!("{}", analyze_loudness(paths, &target, &backend)?);
             }
             "show-chapters" => {
                 let path: &String = args.get_one("path").unwrap();
-                show_chapters(path)?;
+                println!("{}", show_chapters(path, &ffprobe_backend(args)?)?);
             }
             "chapters-to-toml" => {
                 let path: &String = args.get_one("path").unwrap();
-                chapters_to_toml(path)?;
+                print!("{}", chapters_to_toml(path, &ffprobe_backend(args)?)?);
             }
             "toml-to-chapters" => {
                 let path: &String = args.get_one("path").unwrap();
                 let toml: &String = args.get_one("toml").unwrap();
                 let output: &String = args.get_one("output").unwrap();
-                let ffmpeg_path: &String = args.get_one("ffmpeg-path").unwrap();
-                toml_to_chapters(path, output, toml, &ffmpeg_path)?;
+                let backend: FfmpegBackend = ffmpeg_backend(args, &[])?;
+                toml_to_chapters(path, output, toml, &backend)?;
+            }
+            "split" => {
+                let path: &String = args.get_one("path").unwrap();
+                let options: SplitOptions = split_options(args)?;
+                let backend: FfmpegBackend =
+                    ffmpeg_backend(args, &[options.encoder().codec().encoder()])?;
+                split_file(path, &options, &backend)?;
+            }
+            "split-parts" => {
+                let path: &String = args.get_one("path").unwrap();
+                let options: PartOptions = part_options(args);
+                let backend: FfmpegBackend = ffmpeg_backend(args, &[])?;
+                for (part, number) in split_parts(path, &options, &backend)?.iter().zip(1..) {
+                    if part.exceeds_limit {
+                        eprintln!(
+                            "Warning: Part {number} exceeds the limit, since the chapter '{}' \
+                            cannot be split",
+                            part.chapters[0].title()
+                        );
+                    }
+                }
             }
-            "example-toml" => example_toml(),
+            "doctor" => {
+                let ffmpeg_path: Option<&String> = args.get_one("ffmpeg-path");
+                let report = doctor(ffmpeg_path.map(String::as_str))?;
+                print!("{report}");
+                if let Some(problem) = report.problem {
+                    return Err(problem.into());
+                }
+            }
+            "example-toml" => print!("{}", example_toml()),
             _ => {}
         }
     }
     Ok(())
 }
 
+fn ffmpeg_backend(args: &ArgMatches, required_encoders: &[&str]) -> anyhow::Result<FfmpegBackend> {
+    let locator = tool_locator(args)?;
+    locator.check_ffmpeg(required_encoders)?;
+    configured_backend(args, &locator)
+}
+
+// For commands that only read files with ffprobe and never start ffmpeg.
+fn ffprobe_backend(args: &ArgMatches) -> anyhow::Result<FfmpegBackend> {
+    configured_backend(args, &tool_locator(args)?)
+}
+
+fn tool_locator(args: &ArgMatches) -> anyhow::Result<ToolLocator> {
+    let ffmpeg_path = args.try_get_one::<String>("ffmpeg-path").ok().flatten();
+    Ok(ToolLocator::locate(ffmpeg_path.map(String::as_str))?)
+}
+
+fn configured_backend(args: &ArgMatches, locator: &ToolLocator) -> anyhow::Result<FfmpegBackend> {
+    locator.check_ffprobe()?;
+    let mut backend = locator.backend();
+    backend.set_quiet(args.get_flag("quiet"));
+    backend.set_overwrite(args.get_flag("overwrite"));
+    let progress_bar: Mutex<Option<ProgressBar>> = Mutex::new(None);
+    backend.set_reporter(move |event| report(&progress_bar, event));
+    Ok(backend)
+}
+
+fn report(progress_bar: &Mutex<Option<ProgressBar>>, event: Event) {
+    let mut progress_bar = progress_bar.lock().unwrap();
+    match event {
+        Event::Status(message) => println!("{message}"),
+        Event::Progress {
+            position,
+            total,
+            speed,
+        } => {
+            let bar = progress_bar.get_or_insert_with(|| ProgressBar::new(Some(total)));
+            eprint!("\r{}", bar.line(position, speed));
+            io::stderr().flush().ok();
+        }
+        Event::ProgressEnd => {
+            if progress_bar.take().is_some() {
+                eprintln!();
+            }
+        }
+    }
+}
+
+fn encoder_settings(args: &ArgMatches) -> anyhow::Result<EncoderSettings> {
+    let preset: &String = args.get_one("preset").unwrap();
+    let mut encoder = EncoderSettings::preset(preset)?;
+    if let Some(codec) = args.get_one::<String>("codec") {
+        encoder.set_codec(codec.parse::<Codec>()?);
+    }
+    if let Some(bitrate) = args.get_one::<u32>("bitrate") {
+        encoder.set_bitrate(*bitrate);
+    }
+    if let Some(quality) = args.get_one::<f32>("quality") {
+        encoder.set_quality(*quality);
+    }
+    if let Some(channels) = args.get_one::<u32>("channels") {
+        encoder.set_channels(*channels);
+    }
+    if let Some(sample_rate) = args.get_one::<u32>("sample-rate") {
+        encoder.set_sample_rate(*sample_rate);
+    }
+    Ok(encoder)
+}
+
+fn numbering_options(args: &ArgMatches) -> NumberingOptions {
+    let start: &u32 = args.get_one("start").unwrap();
+    let discs = if args.get_flag("disc-from-folders") {
+        DiscNumbering::Folders
+    } else if let Some(pattern) = args.get_one::<String>("disc-pattern") {
+        DiscNumbering::Pattern(pattern.clone())
+    } else {
+        DiscNumbering::Single
+    };
+    NumberingOptions::new()
+        .start(*start)
+        .discs(discs)
+        .continuous(args.get_flag("continuous"))
+}
+
+fn combine_options(args: &ArgMatches) -> anyhow::Result<CombineOptions> {
+    let gap: &u32 = args.get_one("gap").unwrap();
+    let jobs: &usize = args.get_one("jobs").unwrap();
+    let mut options = CombineOptions::new()
+        .encoder(encoder_settings(args)?)
+        .metadata(book_metadata(args))
+        .allow_stream_copy(!args.get_flag("reencode"))
+        .explicit_quality(args.contains_id("bitrate") || args.contains_id("quality"))
+        .gap(*gap)
+        .jobs(*jobs);
+    if args.get_flag("normalize") {
+        options = options.normalize(loudness_target(args));
+    }
+    if args.get_flag("trim-silence") {
+        options = options.trim_silence(silence_settings(args));
+    }
+    if let Some(title) = args.get_one::<String>("title") {
+        options = options.title(title);
+    }
+    if let Some(author) = args.get_one::<String>("author") {
+        options = options.author(author);
+    }
+    if let Some(narrator) = args.get_one::<String>("narrator") {
+        options = options.narrator(narrator);
+    }
+    if let Some(genre) = args.get_one::<String>("genre") {
+        options = options.genre(genre);
+    }
+    if let Some(work_dir) = args.get_one::<String>("work-dir") {
+        options = options.work_dir(work_dir);
+    }
+    Ok(options)
+}
+
+fn split_options(args: &ArgMatches) -> anyhow::Result<SplitOptions> {
+    let output_dir: &String = args.get_one("output-dir").unwrap();
+    let codec: Codec = args.get_one::<String>("format").unwrap().parse()?;
+    let naming_scheme: &String = args.get_one("naming-scheme").unwrap();
+    let bitrate: &u32 = args.get_one("bitrate").unwrap();
+    let mut options = SplitOptions::new()
+        .output_dir(output_dir)
+        .codec(codec)
+        .naming_scheme(naming_scheme)
+        .bitrate(*bitrate);
+    if let Some(title) = args.get_one::<String>("title") {
+        options = options.title(title);
+    }
+    if let Some(author) = args.get_one::<String>("author") {
+        options = options.author(author);
+    }
+    if let Some(narrator) = args.get_one::<String>("narrator") {
+        options = options.narrator(narrator);
+    }
+    Ok(options)
+}
+
+fn part_options(args: &ArgMatches) -> PartOptions {
+    let output_dir: &String = args.get_one("output-dir").unwrap();
+    let naming_scheme: &String = args.get_one("naming-scheme").unwrap();
+    let mut options = PartOptions::new()
+        .output_dir(output_dir)
+        .naming_scheme(naming_scheme);
+    if let Some(max_duration) = args.get_one::<u32>("max-duration") {
+        options = options.max_duration(*max_duration);
+    }
+    if let Some(max_size) = args.get_one::<u64>("max-size") {
+        options = options.max_size(*max_size);
+    }
+    options
+}
+
+fn book_metadata(args: &ArgMatches) -> BookMetadata {
+    let mut metadata = BookMetadata::new();
+    if let Some(series) = args.get_one::<String>("series") {
+        metadata.set_series(series);
+    }
+    if let Some(series_index) = args.get_one::<String>("series-index") {
+        metadata.set_series_index(series_index);
+    }
+    if let Some(description) = args.get_one::<String>("description") {
+        metadata.set_description(description);
+    }
+    if let Some(year) = args.get_one::<u32>("year") {
+        metadata.set_year(*year);
+    }
+    if let Some(publisher) = args.get_one::<String>("publisher") {
+        metadata.set_publisher(publisher);
+    }
+    if let Some(language) = args.get_one::<String>("language") {
+        metadata.set_language(language);
+    }
+    if let Some(isbn) = args.get_one::<String>("isbn") {
+        metadata.set_isbn(isbn);
+    }
+    if let Some(asin) = args.get_one::<String>("asin") {
+        metadata.set_asin(asin);
+    }
+    metadata
+}
+
+fn loudness_target(args: &ArgMatches) -> LoudnessTarget {
+    let integrated: &f64 = args.get_one("target-lufs").unwrap();
+    let true_peak: &f64 = args.get_one("true-peak").unwrap();
+    LoudnessTarget::new(*integrated, *true_peak)
+}
+
+fn silence_settings(args: &ArgMatches) -> SilenceSettings {
+    let threshold: &f64 = args.get_one("silence-threshold").unwrap();
+    let min_duration: &u32 = args.get_one("min-silence").unwrap();
+    SilenceSettings::new(*threshold, *min_duration)
+}
+
+fn input_paths(args: &ArgMatches, extensions: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for path in args.get_many::<String>("paths").into_iter().flatten() {
+        if path == "-" {
+            paths.extend(read_stdin_paths()?);
+        } else {
+            paths.push(PathBuf::from(path));
+        }
+    }
+    for list in args.get_many::<String>("from-file").into_iter().flatten() {
+        if list == "-" {
+            paths.extend(read_stdin_paths()?);
+        } else {
+            paths.extend(read_path_file(list)?);
+        }
+    }
+
+    let mut filter = PathFilter::new()
+        .follow_symlinks(args.get_flag("follow-symlinks"))
+        .extensions(extensions);
+    for pattern in args.get_many::<String>("include").into_iter().flatten() {
+        filter = filter.include(pattern)?;
+    }
+    for pattern in args.get_many::<String>("exclude").into_iter().flatten() {
+        filter = filter.exclude(pattern)?;
+    }
+    Ok(collect_paths(paths, &filter)?)
+}
+
+fn path_args() -> [Arg; 5] {
+    [
+        Arg::new("paths")
+            .help(
+                "Files, directories or glob patterns to process, in order. \
+                Directories are searched recursively for audio files, or only \
+                mp3, wav and aiff files for commands that edit ID3 tags, skipping \
+                hidden files and folders whose names start with a dot. Use - \
+                to read paths from stdin, one per line or NUL-delimited",
+            )
+            .required_unless_present("from-file")
+            .num_args(1..),
+        Arg::new("from-file")
+            .long("from-file")
+            .help("Read paths from a file, one per line or NUL-delimited")
+            .action(ArgAction::Append),
+        Arg::new("include")
+            .long("include")
+            .help("Only process files whose path matches this glob pattern")
+            .action(ArgAction::Append),
+        Arg::new("exclude")
+            .long("exclude")
+            .help("Skip files whose path matches this glob pattern")
+            .action(ArgAction::Append),
+        Arg::new("follow-symlinks")
+            .long("follow-symlinks")
+            .help(
+                "Follow symbolic links to directories when searching for audio \
+                files. Links to files are always followed",
+            )
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn batch_mode(args: &ArgMatches) -> BatchMode {
+    if args.get_flag("keep-going") {
+        BatchMode::KeepGoing
+    } else {
+        BatchMode::FailFast
+    }
+}
+
+fn finish_batch(args: &ArgMatches, report: BatchReport) -> anyhow::Result<()> {
+    if args.get_flag("keep-going") {
+        println!("{}", report);
+    }
+    report.check()?;
+    Ok(())
+}
+
+fn numbering_args() -> [Arg; 4] {
+    [
+        Arg::new("start")
+            .long("start")
+            .short('s')
+            .value_parser(value_parser!(u32))
+            .default_value("1"),
+        Arg::new("disc-from-folders")
+            .long("disc-from-folders")
+            .help("Number the discs by folder, and restart the track numbers of each disc")
+            .action(ArgAction::SetTrue),
+        Arg::new("disc-pattern")
+            .long("disc-pattern")
+            .help(
+                "Read the disc number from the file path, where %d matches \
+                the number (e.g. 'CD%d' or 'Disc %d')",
+            )
+            .conflicts_with("disc-from-folders"),
+        Arg::new("continuous")
+            .long("continuous")
+            .help(
+                "Keep counting the track numbers across discs, for players \
+                that ignore the disc number",
+            )
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn batch_args() -> [Arg; 2] {
+    [
+        Arg::new("keep-going")
+            .long("keep-going")
+            .help(
+                "Process every file even if some of them fail, and print \
+                a summary of the results",
+            )
+            .action(ArgAction::SetTrue),
+        Arg::new("fail-fast")
+            .long("fail-fast")
+            .help(
+                "Check that every file can be read and written before \
+                changing any of them, and stop at the first error (default)",
+            )
+            .conflicts_with("keep-going")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn book_metadata_args() -> [Arg; 8] {
+    [
+        Arg::new("series")
+            .long("series")
+            .help("Name of the series the book belongs to"),
+        Arg::new("series-index")
+            .long("series-index")
+            .help("Position of the book in the series, e.g. 3 or 2.5. Not stored in m4b files"),
+        Arg::new("description")
+            .long("description")
+            .help("Description or synopsis of the book"),
+        Arg::new("year")
+            .long("year")
+            .help("Year the book was published")
+            .value_parser(value_parser!(u32)),
+        Arg::new("publisher")
+            .long("publisher")
+            .help("Publisher of the book. Not stored in m4b files"),
+        Arg::new("language").long("language").help(
+            "Language of the book, as an ISO 639-2 code such as 'eng'. Not stored in m4b files",
+        ),
+        Arg::new("isbn")
+            .long("isbn")
+            .help("ISBN of the book. Not stored in m4b files"),
+        Arg::new("asin")
+            .long("asin")
+            .help("Audible product ID. Not stored in m4b files"),
+    ]
+}
+
+fn loudness_args() -> [Arg; 2] {
+    [
+        Arg::new("target-lufs")
+            .long("target-lufs")
+            .allow_negative_numbers(true)
+            .value_parser(value_parser!(f64))
+            .default_value("-18"),
+        Arg::new("true-peak")
+            .long("true-peak")
+            .allow_negative_numbers(true)
+            .value_parser(value_parser!(f64))
+            .default_value("-1.5"),
+    ]
+}
+
 fn cli() -> ArgMatches {
-    let matches = command!()
+    command!()
         .subcommand_required(true)
         .about(
             "Tool to prepare audiobook files by changing metadata and \
             combining multiple mp3 files into one m4b",
         )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .help("Hide the ffmpeg progress bar and output")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .help("Replace existing output files instead of stopping with an error")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
         .subcommand(
             Command::new("show-tags")
                 .about("Show common ID3 tags from files.")
-                .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
-                ),
+                .args(path_args()),
         )
         .subcommand(
             Command::new("number-files")
@@ -93,43 +592,85 @@ fn cli() -> ArgMatches {
                     number, starting from the specified value. The starting \
                     number must be positiv or zero",
                 )
+                .args(path_args())
+                .args(numbering_args())
                 .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
+                    Arg::new("with-total")
+                        .long("with-total")
+                        .help("Write the track number as n/total")
+                        .action(ArgAction::SetTrue),
                 )
-                .arg(
-                    Arg::new("start")
-                        .long("start")
-                        .short('s')
-                        .value_parser(value_parser!(u32))
-                        .default_value("1"),
-                ),
+                .args(batch_args()),
         )
         .subcommand(
             Command::new("number-file-titles")
                 .about(
                     "Update the title tag of each file with a name based on a \
-                    naming scheme, replacing '%n' with a sequential number, \
-                    starting from specified value.",
+                    naming scheme. '%n' is replaced with a sequential number \
+                    starting from the specified value, '%03n' with the number \
+                    padded to three digits, '%N' with the total, '%d' and '%D' \
+                    with the disc number and total, '%R' and '%r' with upper- or \
+                    lowercase roman numerals, '%w' with the number spelled out, \
+                    '%t' with the current title and '%f' with the file name \
+                    without extension.",
                 )
                 .arg(Arg::new("naming-scheme").required(true))
+                .args(path_args())
+                .args(numbering_args())
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("tags-from-filename")
+                .about(
+                    "Set tags from the file names, parsed with a pattern where \
+                    '%a' is the author, '%b' the book (album), '%n' the track, \
+                    '%d' the disc, '%t' the title, '%c' the narrator \
+                    (composer), '%y' the year and '%x' is ignored. For \
+                    example: '%a - %b - %n - %t'",
+                )
+                .arg(Arg::new("pattern").required(true))
+                .args(path_args())
                 .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show the resulting tags without changing any files")
+                        .action(ArgAction::SetTrue),
+                )
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about(
+                    "Rename or move files to a path built from their tags, for \
+                    example '{author}/{series}/{series_index} - {title}/{track:02} \
+                    - {chapter}.mp3'. The fields are author, album_artist, \
+                    narrator, title (the book), chapter, track, disc, year, \
+                    series and series_index, and ':02' pads numbers to two \
+                    digits. Nothing is renamed if any file is missing a field \
+                    or two files would end up with the same name.",
                 )
+                .arg(Arg::new("template").required(true))
+                .args(path_args())
                 .arg(
-                    Arg::new("start")
-                        .long("start")
-                        .short('s')
-                        .value_parser(value_parser!(i32))
-                        .default_value("1"),
+                    Arg::new("output-dir")
+                        .long("output-dir")
+                        .short('o')
+                        .help("Directory the template paths are relative to")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show the planned renames without changing any files")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
             Command::new("change-title")
                 .about("Change the title tag of each specified file to the given title.")
                 .arg(Arg::new("title").required(true))
-                .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
-                ),
+                .args(path_args())
+                .args(batch_args()),
         )
         .subcommand(
             Command::new("change-author")
@@ -138,9 +679,8 @@ fn cli() -> ArgMatches {
                     file to the given author name.",
                 )
                 .arg(Arg::new("author").required(true))
-                .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
-                ),
+                .args(path_args())
+                .args(batch_args()),
         )
         .subcommand(
             Command::new("change-narrator")
@@ -149,64 +689,247 @@ fn cli() -> ArgMatches {
                     file to the given narrator name.",
                 )
                 .arg(Arg::new("narrator").required(true))
-                .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
-                ),
+                .args(path_args())
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("change-book-info")
+                .about(
+                    "Set book details such as the series, description, year, \
+                    publisher and ISBN on each specified file. Only the given \
+                    details are changed.",
+                )
+                .args(book_metadata_args())
+                .group(
+                    ArgGroup::new("details")
+                        .args([
+                            "series",
+                            "series-index",
+                            "description",
+                            "year",
+                            "publisher",
+                            "language",
+                            "isbn",
+                            "asin",
+                        ])
+                        .multiple(true)
+                        .required(true),
+                )
+                .args(path_args())
+                .args(batch_args()),
         )
         .subcommand(
             Command::new("change-tag")
-                .about("Change a specified tag of each file to the given value.")
+                .about(
+                    "Change a specified tag of each file to the given value. \
+                    Text (T...) and URL (W...) frames take the value as is. \
+                    For TXXX, WXXX, COMM, USLT and POPM frames a description \
+                    (or POPM user) can follow the frame ID, e.g. 'TXXX:SERIES' \
+                    or 'COMM:Source', and POPM takes a rating from 0 to 255. \
+                    Other frames, such as pictures, are rejected.",
+                )
                 .arg(Arg::new("tag").required(true))
                 .arg(Arg::new("value").required(true))
                 .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
-                ),
+                    Arg::new("language")
+                        .long("language")
+                        .help("Three letter language code of COMM and USLT frames (default: eng)"),
+                )
+                .args(path_args())
+                .args(batch_args()),
         )
         .subcommand(
-            Command::new("combine-files")
+            Command::new("remove-tag")
                 .about(
-                    "Combine multiple audio files into a single file, \
-                    with the input files as chapter markers.",
+                    "Remove frames from the ID3v2 tag of each file. Frames are \
+                    given by ID and separated by commas, and a description \
+                    narrows the match, e.g. 'COMM,TXXX:Ripper,PRIV'.",
+                )
+                .arg(Arg::new("frames").required(true).value_delimiter(','))
+                .args(path_args())
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("clear-tags")
+                .about(
+                    "Remove the ID3v2 and ID3v1 tags of each file, keeping only \
+                    the frames given with --keep.",
                 )
                 .arg(
-                    Arg::new("paths").required(true).num_args(1..), // .value_parser(value_parser!(PathBuf))
+                    Arg::new("keep")
+                        .long("keep")
+                        .short('k')
+                        .help(
+                            "Frames to keep, separated by commas, \
+                            e.g. 'TIT2,TALB,TPE1,TXXX:SERIES'",
+                        )
+                        .value_delimiter(',')
+                        .action(ArgAction::Append),
                 )
+                .args(path_args())
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("strip-v1")
+                .about("Remove the ID3v1 tag from the end of each file.")
+                .args(path_args())
+                .args(batch_args()),
+        )
+        .subcommand(
+            Command::new("combine-files")
+                .about(
+                    "Combine multiple audio files into a single file, \
+                    with the input files as chapter markers. The book tags are \
+                    taken from the tags the input files agree on, unless they \
+                    are given as options.",
+                )
+                .args(path_args())
                 .arg(
                     Arg::new("output")
                         .long("output")
                         .short('o')
                         .default_value("./output.m4b"), // .value_parser(value_parser!(PathBuf))
                 )
+                .arg(
+                    Arg::new("preset")
+                        .long("preset")
+                        .short('p')
+                        .value_parser(PRESETS)
+                        .default_value("standard"),
+                )
+                .arg(Arg::new("codec").long("codec").short('c').value_parser([
+                    "aac",
+                    "libfdk_aac",
+                    "opus",
+                    "mp3",
+                    "copy",
+                ]))
                 .arg(
                     Arg::new("bitrate")
                         .long("bitrate")
                         .short('b')
-                        .default_value("64")
+                        .value_parser(value_parser!(u32))
+                        .conflicts_with("quality"),
+                )
+                .arg(
+                    Arg::new("quality")
+                        .long("quality")
+                        .short('q')
+                        .value_parser(value_parser!(f32)),
+                )
+                .arg(
+                    Arg::new("channels")
+                        .long("channels")
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("sample-rate")
+                        .long("sample-rate")
                         .value_parser(value_parser!(u32)),
                 )
+                .arg(
+                    Arg::new("reencode")
+                        .long("reencode")
+                        .help(
+                            "Always re-encode, even if the inputs could be \
+                            stream copied into the output file",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("normalize")
+                        .long("normalize")
+                        .help(
+                            "Normalize the loudness of each input file to the \
+                            target loudness using two-pass EBU R128 loudnorm",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .args(loudness_args())
+                .arg(
+                    Arg::new("trim-silence")
+                        .long("trim-silence")
+                        .help("Trim leading and trailing silence from each input file")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("silence-threshold")
+                        .long("silence-threshold")
+                        .help("Noise level in dB below which audio counts as silence")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f64))
+                        .default_value("-50"),
+                )
+                .arg(
+                    Arg::new("min-silence")
+                        .long("min-silence")
+                        .help("Minimum duration in milliseconds of silence to trim")
+                        .value_parser(value_parser!(u32))
+                        .default_value("500"),
+                )
+                .arg(
+                    Arg::new("gap")
+                        .long("gap")
+                        .help("Silence in milliseconds to insert between input files")
+                        .value_parser(value_parser!(u32))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .help(
+                            "Number of input files to encode in parallel. With more \
+                            than one job, the inputs are encoded to intermediate \
+                            segments that are joined without re-encoding",
+                        )
+                        .value_parser(value_parser!(usize))
+                        .default_value("1"),
+                )
+                .arg(Arg::new("work-dir").long("work-dir").help(
+                    "Directory to keep encoded segments in, so an interrupted \
+                            or repeated run only re-encodes the input files that changed",
+                ))
                 .arg(
                     Arg::new("title")
                         .long("title")
                         .short('t')
-                        .default_value("Unknown title"),
+                        .help("Book title, instead of the album tag shared by the input files"),
                 )
                 .arg(
                     Arg::new("author")
                         .long("author")
                         .short('a')
-                        .default_value("Unknown author"),
+                        .help("Author, instead of the artist tag shared by the input files"),
                 )
                 .arg(
-                    Arg::new("ffmpeg-path")
-                        .long("with-ffmpeg")
-                        .short('w')
-                        .default_value("ffmpeg"),
-                ),
+                    Arg::new("narrator")
+                        .long("narrator")
+                        .help("Narrator, instead of the composer tag shared by the input files"),
+                )
+                .arg(
+                    Arg::new("genre")
+                        .long("genre")
+                        .help("Genre, instead of the genre tag shared by the input files"),
+                )
+                .args(book_metadata_args())
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
+        )
+        .subcommand(
+            Command::new("analyze-loudness")
+                .about(
+                    "Measure the EBU R128 integrated loudness, true peak and \
+                    loudness range of each file.",
+                )
+                .args(path_args())
+                .args(loudness_args())
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
         )
         .subcommand(
             Command::new("show-chapters")
                 .about("Show the embedded chapters in an audiobook file (e.g. m4b or mp4)")
-                .arg(Arg::new("path").required(true)),
+                .arg(Arg::new("path").required(true))
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
         )
         .subcommand(
             Command::new("chapters-to-toml")
@@ -214,7 +937,8 @@ fn cli() -> ArgMatches {
                     "Reads embedded chapters from audiobook file and \
                     outputs them to stdout as TOML",
                 )
-                .arg(Arg::new("path").required(true)),
+                .arg(Arg::new("path").required(true))
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
         )
         .subcommand(
             Command::new("toml-to-chapters")
@@ -227,18 +951,103 @@ fn cli() -> ArgMatches {
                         .short('o')
                         .default_value("chaptered.m4b"),
                 )
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
+        )
+        .subcommand(
+            Command::new("split")
+                .about(
+                    "Split a chaptered audiobook file (e.g. m4b) into one file \
+                    per chapter, named from a naming scheme where '%n' is \
+                    replaced with the chapter number, '%t' with the chapter \
+                    title and '%b' with the book title. The naming scheme \
+                    must contain '%n'.",
+                )
+                .arg(Arg::new("path").required(true))
                 .arg(
-                    Arg::new("ffmpeg-path")
-                        .long("with-ffmpeg")
-                        .short('w')
-                        .default_value("ffmpeg"),
-                ),
+                    Arg::new("output-dir")
+                        .long("output-dir")
+                        .short('o')
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["mp3", "m4a", "opus"])
+                        .default_value("mp3"),
+                )
+                .arg(
+                    Arg::new("naming-scheme")
+                        .long("naming-scheme")
+                        .short('n')
+                        .default_value("%n - %t"),
+                )
+                .arg(
+                    Arg::new("bitrate")
+                        .long("bitrate")
+                        .short('b')
+                        .default_value("64")
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(Arg::new("title").long("title").short('t'))
+                .arg(Arg::new("author").long("author").short('a'))
+                .arg(Arg::new("narrator").long("narrator"))
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
+        )
+        .subcommand(
+            Command::new("split-parts")
+                .about(
+                    "Split a chaptered audiobook file into multiple m4b parts, \
+                    each shorter than a maximum duration (in minutes) and/or \
+                    smaller than a maximum size (in MB, estimated from the \
+                    average bitrate). Parts are only split at chapter boundaries.",
+                )
+                .arg(Arg::new("path").required(true))
+                .arg(
+                    Arg::new("output-dir")
+                        .long("output-dir")
+                        .short('o')
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("max-duration")
+                        .long("max-duration")
+                        .short('d')
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("max-size")
+                        .long("max-size")
+                        .short('s')
+                        .value_parser(value_parser!(u64)),
+                )
+                .group(
+                    ArgGroup::new("limit")
+                        .args(["max-duration", "max-size"])
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::new("naming-scheme")
+                        .long("naming-scheme")
+                        .short('n')
+                        .default_value("Part %n"),
+                )
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about(
+                    "Report which ffmpeg and ffprobe executables are used, \
+                    their versions and the available audio encoders. The tools \
+                    are found from --with-ffmpeg, the FFMPEG and FFPROBE \
+                    environment variables, the config file or PATH.",
+                )
+                .arg(Arg::new("ffmpeg-path").long("with-ffmpeg").short('w')),
         )
         .subcommand(Command::new("example-toml").about(
             "Outputs an example TOML to stdout as a template for creating \
                 chapters for an audiobook file",
         ))
-        .get_matches();
-
-    matches
+        .get_matches()
 }
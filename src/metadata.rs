@@ -0,0 +1,294 @@
+use crate::{
+    backend::MediaInfo,
+    frames::{movement_frame, movement_text},
+};
+use id3::{
+    frame::{Comment, ExtendedText},
+    Tag, TagLike,
+};
+use serde::{Deserialize, Serialize};
+
+pub const BOOK_FIELDS: [&str; 8] = [
+    "series",
+    "series index",
+    "description",
+    "year",
+    "publisher",
+    "language",
+    "ISBN",
+    "ASIN",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series_index: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    year: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publisher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    isbn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asin: Option<String>,
+}
+
+impl BookMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn series(&self) -> Option<String> {
+        self.series.clone()
+    }
+
+    pub fn series_index(&self) -> Option<String> {
+        self.series_index.clone()
+    }
+
+    pub fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    pub fn year(&self) -> Option<u32> {
+        self.year
+    }
+
+    pub fn publisher(&self) -> Option<String> {
+        self.publisher.clone()
+    }
+
+    pub fn language(&self) -> Option<String> {
+        self.language.clone()
+    }
+
+    pub fn isbn(&self) -> Option<String> {
+        self.isbn.clone()
+    }
+
+    pub fn asin(&self) -> Option<String> {
+        self.asin.clone()
+    }
+
+    pub fn set_series(&mut self, series: impl Into<String>) {
+        self.series = Some(series.into());
+    }
+
+    pub fn set_series_index(&mut self, series_index: impl Into<String>) {
+        self.series_index = Some(series_index.into());
+    }
+
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.description = Some(description.into());
+    }
+
+    pub fn set_year(&mut self, year: u32) {
+        self.year = Some(year);
+    }
+
+    pub fn set_publisher(&mut self, publisher: impl Into<String>) {
+        self.publisher = Some(publisher.into());
+    }
+
+    pub fn set_language(&mut self, language: impl Into<String>) {
+        self.language = Some(language.into());
+    }
+
+    pub fn set_isbn(&mut self, isbn: impl Into<String>) {
+        self.isbn = Some(isbn.into());
+    }
+
+    pub fn set_asin(&mut self, asin: impl Into<String>) {
+        self.asin = Some(asin.into());
+    }
+
+    pub fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "series" => self.series(),
+            "series index" => self.series_index(),
+            "description" => self.description(),
+            "year" => self.year.map(|year| year.to_string()),
+            "publisher" => self.publisher(),
+            "language" => self.language(),
+            "ISBN" => self.isbn(),
+            "ASIN" => self.asin(),
+            _ => None,
+        }
+    }
+
+    pub fn set_field(&mut self, name: &str, value: String) {
+        match name {
+            "series" => self.series = Some(value),
+            "series index" => self.series_index = Some(value),
+            "description" => self.description = Some(value),
+            "year" => self.year = value.parse().ok(),
+            "publisher" => self.publisher = Some(value),
+            "language" => self.language = Some(value),
+            "ISBN" => self.isbn = Some(value),
+            "ASIN" => self.asin = Some(value),
+            _ => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn or(self, other: BookMetadata) -> Self {
+        Self {
+            series: self.series.or(other.series),
+            series_index: self.series_index.or(other.series_index),
+            description: self.description.or(other.description),
+            year: self.year.or(other.year),
+            publisher: self.publisher.or(other.publisher),
+            language: self.language.or(other.language),
+            isbn: self.isbn.or(other.isbn),
+            asin: self.asin.or(other.asin),
+        }
+    }
+
+    pub fn from_tag(tag: &Tag) -> Self {
+        let text = |frame_id: &str| {
+            tag.get(frame_id)
+                .and_then(|frame| frame.content().text())
+                .map(str::to_string)
+        };
+        let extended = |description: &str| {
+            tag.extended_texts()
+                .find(|extended| extended.description.eq_ignore_ascii_case(description))
+                .map(|extended| extended.value.clone())
+        };
+        Self {
+            series: extended("SERIES").or_else(|| movement_text(tag, "MVNM")),
+            series_index: extended("SERIES-PART").or_else(|| movement_text(tag, "MVIN")),
+            description: tag
+                .comments()
+                .find(|comment| comment.description.is_empty())
+                .map(|comment| comment.text.clone()),
+            year: tag.year().and_then(|year| u32::try_from(year).ok()),
+            publisher: text("TPUB"),
+            language: text("TLAN"),
+            isbn: extended("ISBN"),
+            asin: extended("ASIN"),
+        }
+    }
+
+    pub fn from_media_info(media_info: &MediaInfo) -> Self {
+        let tag = |key: &str| media_info.tag(key).map(str::to_string);
+        Self {
+            series: tag("series").or_else(|| tag("grouping")),
+            series_index: tag("series-part"),
+            description: tag("description")
+                .or_else(|| tag("synopsis"))
+                .or_else(|| tag("comment")),
+            year: media_info
+                .tag("date")
+                .and_then(|date| date.get(..4))
+                .and_then(|year| year.parse().ok()),
+            publisher: tag("publisher"),
+            language: tag("language"),
+            isbn: tag("isbn"),
+            asin: tag("asin"),
+        }
+    }
+
+    pub fn write_to_tag(&self, tag: &mut Tag) {
+        if let Some(series) = &self.series {
+            tag.add_frame(ExtendedText {
+                description: "SERIES".to_string(),
+                value: series.clone(),
+            });
+            tag.remove("MVNM");
+            tag.add_frame(movement_frame("MVNM", series));
+        }
+        if let Some(series_index) = &self.series_index {
+            tag.add_frame(ExtendedText {
+                description: "SERIES-PART".to_string(),
+                value: series_index.clone(),
+            });
+            tag.remove("MVIN");
+            tag.add_frame(movement_frame("MVIN", series_index));
+        }
+        if let Some(description) = &self.description {
+            tag.add_frame(Comment {
+                lang: "eng".to_string(),
+                description: String::new(),
+                text: description.clone(),
+            });
+        }
+        if let Some(year) = self.year {
+            tag.set_year(year as i32);
+        }
+        if let Some(publisher) = &self.publisher {
+            tag.set_text("TPUB", publisher.as_str());
+        }
+        if let Some(language) = &self.language {
+            tag.set_text("TLAN", language.as_str());
+        }
+        if let Some(isbn) = &self.isbn {
+            tag.add_frame(ExtendedText {
+                description: "ISBN".to_string(),
+                value: isbn.clone(),
+            });
+        }
+        if let Some(asin) = &self.asin {
+            tag.add_frame(ExtendedText {
+                description: "ASIN".to_string(),
+                value: asin.clone(),
+            });
+        }
+    }
+
+    // The mp4 muxer only keeps keys it has an iTunes atom for: grouping (©grp),
+    // description (desc), synopsis (ldes) and date (©day). The series index,
+    // publisher, language, ISBN and ASIN are dropped from m4b outputs and only
+    // survive in mp3 outputs, where ffmpeg writes them as TXXX frames.
+    pub fn ffmetadata(&self) -> String {
+        let mut entries: Vec<(&str, String)> = Vec::new();
+        if let Some(series) = &self.series {
+            entries.push(("grouping", series.clone()));
+            entries.push(("series", series.clone()));
+        }
+        if let Some(series_index) = &self.series_index {
+            entries.push(("series-part", series_index.clone()));
+        }
+        if let Some(description) = &self.description {
+            entries.push(("description", description.clone()));
+            entries.push(("synopsis", description.clone()));
+        }
+        if let Some(year) = self.year {
+            entries.push(("date", year.to_string()));
+        }
+        for (key, value) in [
+            ("publisher", &self.publisher),
+            ("language", &self.language),
+            ("isbn", &self.isbn),
+            ("asin", &self.asin),
+        ] {
+            if let Some(value) = value {
+                entries.push((key, value.clone()));
+            }
+        }
+        entries
+            .into_iter()
+            .map(|(key, value)| format!("{key}={}\n", escape_ffmetadata(&value)))
+            .collect()
+    }
+}
+
+pub fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,480 @@
+use crate::{options::NumberingOptions, Error, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DiscNumbering {
+    #[default]
+    Single,
+    Folders,
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackNumber {
+    pub track: u32,
+    pub total_tracks: Option<u32>,
+    pub disc: Option<u32>,
+    pub total_discs: Option<u32>,
+}
+
+pub fn assign_numbers(paths: &[PathBuf], options: &NumberingOptions) -> Result<Vec<TrackNumber>> {
+    let discs: Vec<u32> = match &options.discs {
+        DiscNumbering::Single => {
+            let total_tracks = (options.start + paths.len() as u32).saturating_sub(1);
+            return Ok((options.start..)
+                .zip(paths)
+                .map(|(track, _)| TrackNumber {
+                    track,
+                    total_tracks: options.totals.then_some(total_tracks),
+                    ..Default::default()
+                })
+                .collect());
+        }
+        DiscNumbering::Folders => {
+            let mut folders: Vec<Option<&Path>> = Vec::new();
+            paths
+                .iter()
+                .map(|path| {
+                    let folder = path.parent();
+                    match folders.iter().position(|known| *known == folder) {
+                        Some(i) => i as u32 + 1,
+                        None => {
+                            folders.push(folder);
+                            folders.len() as u32
+                        }
+                    }
+                })
+                .collect()
+        }
+        DiscNumbering::Pattern(pattern) => paths
+            .iter()
+            .map(|path| disc_from_pattern(path, pattern))
+            .collect::<Result<_>>()?,
+    };
+
+    let mut disc_sizes: BTreeMap<u32, u32> = BTreeMap::new();
+    for disc in &discs {
+        *disc_sizes.entry(*disc).or_default() += 1;
+    }
+    let total_discs = discs.iter().copied().max().unwrap_or_default();
+    let mut disc_positions: BTreeMap<u32, u32> = BTreeMap::new();
+
+    Ok(discs
+        .iter()
+        .enumerate()
+        .map(|(i, disc)| {
+            let position = disc_positions.entry(*disc).or_default();
+            *position += 1;
+            let (track, total_tracks) = if options.continuous {
+                (options.start + i as u32, paths.len() as u32)
+            } else {
+                (options.start + *position - 1, disc_sizes[disc])
+            };
+            let total_tracks = (options.start + total_tracks).saturating_sub(1);
+            TrackNumber {
+                track,
+                total_tracks: Some(total_tracks),
+                disc: Some(*disc),
+                total_discs: Some(total_discs),
+            }
+        })
+        .collect())
+}
+
+fn disc_from_pattern(path: &Path, pattern: &str) -> Result<u32> {
+    let (prefix, suffix) = pattern
+        .split_once("%d")
+        .ok_or(Error::NoFormatSpecifierError("%d".to_string()))?;
+    let prefix = prefix.to_lowercase();
+    let suffix = suffix.to_lowercase();
+    let text = path.to_string_lossy().to_lowercase();
+
+    for (start, _) in text
+        .match_indices(&prefix)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        if prefix.is_empty() && text[..start].ends_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let rest = &text[start + prefix.len()..];
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        if digits.is_empty() || !rest[digits.len()..].starts_with(&suffix) {
+            continue;
+        }
+        if let Ok(disc) = digits.parse() {
+            return Ok(disc);
+        }
+    }
+    Err(Error::DiscPatternError {
+        path: path.to_path_buf(),
+        pattern: pattern.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Number { specifier: char, width: usize },
+    Roman { lowercase: bool },
+    Words,
+    Title,
+    FileStem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl TitleTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts: Vec<TemplatePart> = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let mut width = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                width.push(digit);
+            }
+            let part = match chars.next() {
+                Some('%') if width.is_empty() => {
+                    text.push('%');
+                    continue;
+                }
+                Some(specifier @ ('n' | 'N' | 'd' | 'D')) => TemplatePart::Number {
+                    specifier,
+                    width: width.parse().unwrap_or_default(),
+                },
+                Some('R') if width.is_empty() => TemplatePart::Roman { lowercase: false },
+                Some('r') if width.is_empty() => TemplatePart::Roman { lowercase: true },
+                Some('w') if width.is_empty() => TemplatePart::Words,
+                Some('t') if width.is_empty() => TemplatePart::Title,
+                Some('f') if width.is_empty() => TemplatePart::FileStem,
+                other => {
+                    return Err(Error::UnknownFormatSpecifierError(format!(
+                        "%{width}{}",
+                        other.map(String::from).unwrap_or_default()
+                    )))
+                }
+            };
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(part);
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        let numbered = parts.iter().any(|part| {
+            matches!(
+                part,
+                TemplatePart::Number { specifier: 'n', .. }
+                    | TemplatePart::Roman { .. }
+                    | TemplatePart::Words
+            )
+        });
+        if !numbered {
+            return Err(Error::NoFormatSpecifierError("%n".to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, number: &TrackNumber, title: &str, file_stem: &str) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => rendered.push_str(text),
+                TemplatePart::Number { specifier, width } => {
+                    let value = match specifier {
+                        'n' => number.track,
+                        'N' => number.total_tracks.unwrap_or(number.track),
+                        'd' => number.disc.unwrap_or(1),
+                        _ => number.total_discs.unwrap_or(1),
+                    };
+                    rendered.push_str(&format!("{value:0width$}"));
+                }
+                TemplatePart::Roman { lowercase: false } => {
+                    rendered.push_str(&roman_numeral(number.track))
+                }
+                TemplatePart::Roman { lowercase: true } => {
+                    rendered.push_str(&roman_numeral(number.track).to_lowercase())
+                }
+                TemplatePart::Words => rendered.push_str(&number_words(number.track)),
+                TemplatePart::Title => rendered.push_str(title),
+                TemplatePart::FileStem => rendered.push_str(file_stem),
+            }
+        }
+        rendered
+    }
+}
+
+pub fn roman_numeral(number: u32) -> String {
+    if number == 0 || number >= 4000 {
+        return number.to_string();
+    }
+    let numerals = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut remaining = number;
+    let mut roman = String::new();
+    for (value, numeral) in numerals {
+        while remaining >= value {
+            roman.push_str(numeral);
+            remaining -= value;
+        }
+    }
+    roman
+}
+
+pub fn number_words(number: u32) -> String {
+    const ONES: [&str; 20] = [
+        "Zero",
+        "One",
+        "Two",
+        "Three",
+        "Four",
+        "Five",
+        "Six",
+        "Seven",
+        "Eight",
+        "Nine",
+        "Ten",
+        "Eleven",
+        "Twelve",
+        "Thirteen",
+        "Fourteen",
+        "Fifteen",
+        "Sixteen",
+        "Seventeen",
+        "Eighteen",
+        "Nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "Twenty", "Thirty", "Forty", "Fifty", "Sixty", "Seventy", "Eighty", "Ninety",
+    ];
+
+    match number {
+        0..=19 => ONES[number as usize].to_string(),
+        20..=99 => match number % 10 {
+            0 => TENS[(number / 10) as usize].to_string(),
+            ones => format!("{}-{}", TENS[(number / 10) as usize], ONES[ones as usize]),
+        },
+        100..=999 => match number % 100 {
+            0 => format!("{} Hundred", ONES[(number / 100) as usize]),
+            rest => format!(
+                "{} Hundred {}",
+                ONES[(number / 100) as usize],
+                number_words(rest)
+            ),
+        },
+        _ => {
+            let (scale, name) = if number >= 1_000_000_000 {
+                (1_000_000_000, "Billion")
+            } else if number >= 1_000_000 {
+                (1_000_000, "Million")
+            } else {
+                (1_000, "Thousand")
+            };
+            match number % scale {
+                0 => format!("{} {name}", number_words(number / scale)),
+                rest => format!(
+                    "{} {name} {}",
+                    number_words(number / scale),
+                    number_words(rest)
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn tracks(numbers: &[TrackNumber]) -> Vec<(u32, Option<u32>, Option<u32>)> {
+        numbers
+            .iter()
+            .map(|number| (number.track, number.total_tracks, number.disc))
+            .collect()
+    }
+
+    #[test]
+    fn single_disc_numbers_from_start() {
+        let options = NumberingOptions::new().start(5).totals(true);
+        let numbers = assign_numbers(&paths(&["a.mp3", "b.mp3", "c.mp3"]), &options).unwrap();
+        assert_eq!(
+            tracks(&numbers),
+            [(5, Some(7), None), (6, Some(7), None), (7, Some(7), None)]
+        );
+        let options = NumberingOptions::new();
+        let numbers = assign_numbers(&paths(&["a.mp3"]), &options).unwrap();
+        assert_eq!(tracks(&numbers), [(1, None, None)]);
+    }
+
+    #[test]
+    fn folder_discs_restart_track_numbers() {
+        let paths = paths(&["book/1/a.mp3", "book/1/b.mp3", "book/2/a.mp3"]);
+        let options = NumberingOptions::new().discs(DiscNumbering::Folders);
+        let numbers = assign_numbers(&paths, &options).unwrap();
+        assert_eq!(
+            tracks(&numbers),
+            [
+                (1, Some(2), Some(1)),
+                (2, Some(2), Some(1)),
+                (1, Some(1), Some(2))
+            ]
+        );
+        assert!(numbers.iter().all(|number| number.total_discs == Some(2)));
+
+        let numbers = assign_numbers(&paths, &options.clone().start(10)).unwrap();
+        assert_eq!(
+            tracks(&numbers),
+            [
+                (10, Some(11), Some(1)),
+                (11, Some(11), Some(1)),
+                (10, Some(10), Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn continuous_numbers_run_across_discs() {
+        let paths = paths(&["book/1/a.mp3", "book/1/b.mp3", "book/2/a.mp3"]);
+        let options = NumberingOptions::new()
+            .discs(DiscNumbering::Folders)
+            .continuous(true);
+        let numbers = assign_numbers(&paths, &options).unwrap();
+        assert_eq!(
+            tracks(&numbers),
+            [
+                (1, Some(3), Some(1)),
+                (2, Some(3), Some(1)),
+                (3, Some(3), Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn disc_patterns_find_the_disc_number() {
+        let disc = |path: &str, pattern: &str| disc_from_pattern(Path::new(path), pattern);
+        assert_eq!(disc("book/Book CD2/01.mp3", "CD%d").unwrap(), 2);
+        assert_eq!(disc("book/book cd3/01.mp3", "CD%d").unwrap(), 3);
+        assert_eq!(disc("book/Disc 10/track 4.mp3", "Disc %d").unwrap(), 10);
+        assert_eq!(disc("book/12-03.mp3", "%d-").unwrap(), 12);
+
+        let paths = paths(&["Disc 1/a.mp3", "Disc 2/a.mp3"]);
+        let options = NumberingOptions::new().discs(DiscNumbering::Pattern("Disc %d".to_string()));
+        let numbers = assign_numbers(&paths, &options).unwrap();
+        assert_eq!(
+            tracks(&numbers),
+            [(1, Some(1), Some(1)), (1, Some(1), Some(2))]
+        );
+    }
+
+    #[test]
+    fn disc_patterns_report_missing_numbers() {
+        assert!(matches!(
+            disc_from_pattern(Path::new("book/01.mp3"), "CD%d"),
+            Err(Error::DiscPatternError { .. })
+        ));
+        assert!(matches!(
+            disc_from_pattern(Path::new("book/CD1/01.mp3"), "CD"),
+            Err(Error::NoFormatSpecifierError(_))
+        ));
+    }
+
+    fn render(template: &str, number: TrackNumber) -> String {
+        TitleTemplate::parse(template)
+            .unwrap()
+            .render(&number, "Title", "01 stem")
+    }
+
+    #[test]
+    fn template_pads_numbers() {
+        let number = TrackNumber {
+            track: 7,
+            ..Default::default()
+        };
+        assert_eq!(render("Chapter %3n", number), "Chapter 007");
+        assert_eq!(render("%n: %t (%f)", number), "7: Title (01 stem)");
+    }
+
+    #[test]
+    fn template_renders_totals_and_discs() {
+        let number = TrackNumber {
+            track: 3,
+            total_tracks: Some(12),
+            disc: Some(2),
+            total_discs: Some(4),
+        };
+        assert_eq!(render("%d/%D %2n of %N", number), "2/4 03 of 12");
+        let single = TrackNumber {
+            track: 3,
+            ..Default::default()
+        };
+        assert_eq!(render("%d/%D %n of %N", single), "1/1 3 of 3");
+    }
+
+    #[test]
+    fn template_renders_roman_numerals_and_words() {
+        let number = TrackNumber {
+            track: 14,
+            ..Default::default()
+        };
+        assert_eq!(render("%R %r %w %%", number), "XIV xiv Fourteen %");
+    }
+
+    #[test]
+    fn template_requires_a_track_number() {
+        assert!(matches!(
+            TitleTemplate::parse("Disc %d"),
+            Err(Error::NoFormatSpecifierError(_))
+        ));
+        assert!(matches!(
+            TitleTemplate::parse("%n %2R"),
+            Err(Error::UnknownFormatSpecifierError(_))
+        ));
+    }
+
+    #[test]
+    fn roman_numerals_cover_the_supported_range() {
+        assert_eq!(roman_numeral(0), "0");
+        assert_eq!(roman_numeral(4), "IV");
+        assert_eq!(roman_numeral(3999), "MMMCMXCIX");
+        assert_eq!(roman_numeral(4000), "4000");
+    }
+
+    #[test]
+    fn number_words_spell_out_numbers() {
+        assert_eq!(number_words(12), "Twelve");
+        assert_eq!(number_words(21), "Twenty-One");
+        assert_eq!(number_words(100), "One Hundred");
+        assert_eq!(number_words(1000), "One Thousand");
+    }
+}
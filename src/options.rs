@@ -0,0 +1,370 @@
+use crate::{
+    encoder::{Codec, EncoderSettings},
+    helper::AUDIO_EXTENSIONS,
+    loudness::LoudnessTarget,
+    metadata::BookMetadata,
+    numbering::DiscNumbering,
+    processing::SilenceSettings,
+    Result,
+};
+use glob::Pattern;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BatchMode {
+    #[default]
+    FailFast,
+    KeepGoing,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathFilter {
+    pub(crate) include: Vec<Pattern>,
+    pub(crate) exclude: Vec<Pattern>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) extensions: Vec<String>,
+}
+
+impl PathFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Extensions of the files picked up when walking a directory. Defaults
+    /// to every supported audio format.
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions
+            .iter()
+            .map(|extension| extension.to_string())
+            .collect();
+        self
+    }
+
+    pub(crate) fn directory_extensions(&self) -> Vec<&str> {
+        match self.extensions.is_empty() {
+            true => AUDIO_EXTENSIONS.to_vec(),
+            false => self.extensions.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| pattern.matches_path(path));
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches_path(path))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberingOptions {
+    pub(crate) start: u32,
+    pub(crate) discs: DiscNumbering,
+    pub(crate) continuous: bool,
+    pub(crate) totals: bool,
+}
+
+impl NumberingOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(mut self, start: u32) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn discs(mut self, discs: DiscNumbering) -> Self {
+        self.discs = discs;
+        self
+    }
+
+    pub fn continuous(mut self, continuous: bool) -> Self {
+        self.continuous = continuous;
+        self
+    }
+
+    pub fn totals(mut self, totals: bool) -> Self {
+        self.totals = totals;
+        self
+    }
+}
+
+impl Default for NumberingOptions {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            discs: DiscNumbering::Single,
+            continuous: false,
+            totals: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombineOptions {
+    pub(crate) encoder: EncoderSettings,
+    pub(crate) title: Option<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) narrator: Option<String>,
+    pub(crate) genre: Option<String>,
+    pub(crate) metadata: BookMetadata,
+    pub(crate) allow_stream_copy: bool,
+    pub(crate) explicit_quality: bool,
+    pub(crate) normalization: Option<LoudnessTarget>,
+    pub(crate) silence: Option<SilenceSettings>,
+    pub(crate) gap: u32,
+    pub(crate) jobs: usize,
+    pub(crate) work_dir: Option<PathBuf>,
+}
+
+impl CombineOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encoder(mut self, encoder: EncoderSettings) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.encoder.set_codec(codec);
+        self
+    }
+
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.encoder.set_bitrate(bitrate);
+        self.explicit_quality = true;
+        self
+    }
+
+    pub fn quality(mut self, quality: f32) -> Self {
+        self.encoder.set_quality(quality);
+        self.explicit_quality = true;
+        self
+    }
+
+    /// Marks the encoder's bitrate or quality as chosen by the user, which
+    /// rules out stream copying inputs that already match the codec.
+    pub fn explicit_quality(mut self, explicit_quality: bool) -> Self {
+        self.explicit_quality = explicit_quality;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn narrator(mut self, narrator: impl Into<String>) -> Self {
+        self.narrator = Some(narrator.into());
+        self
+    }
+
+    pub fn genre(mut self, genre: impl Into<String>) -> Self {
+        self.genre = Some(genre.into());
+        self
+    }
+
+    pub fn metadata(mut self, metadata: BookMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn allow_stream_copy(mut self, allow_stream_copy: bool) -> Self {
+        self.allow_stream_copy = allow_stream_copy;
+        self
+    }
+
+    pub fn normalize(mut self, target: LoudnessTarget) -> Self {
+        self.normalization = Some(target);
+        self
+    }
+
+    pub fn trim_silence(mut self, settings: SilenceSettings) -> Self {
+        self.silence = Some(settings);
+        self
+    }
+
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn work_dir(mut self, work_dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = Some(work_dir.into());
+        self
+    }
+
+    pub fn encoder_settings(&self) -> &EncoderSettings {
+        &self.encoder
+    }
+}
+
+impl Default for CombineOptions {
+    fn default() -> Self {
+        Self {
+            encoder: EncoderSettings::default(),
+            title: None,
+            author: None,
+            narrator: None,
+            genre: None,
+            metadata: BookMetadata::default(),
+            allow_stream_copy: true,
+            explicit_quality: false,
+            normalization: None,
+            silence: None,
+            gap: 0,
+            jobs: 1,
+            work_dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitOptions {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) codec: Codec,
+    pub(crate) naming_scheme: String,
+    pub(crate) bitrate: u32,
+    pub(crate) title: Option<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) narrator: Option<String>,
+}
+
+impl SplitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn naming_scheme(mut self, naming_scheme: impl Into<String>) -> Self {
+        self.naming_scheme = naming_scheme.into();
+        self
+    }
+
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = bitrate;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn narrator(mut self, narrator: impl Into<String>) -> Self {
+        self.narrator = Some(narrator.into());
+        self
+    }
+
+    pub fn encoder(&self) -> EncoderSettings {
+        let mut encoder = EncoderSettings::new(self.codec);
+        encoder.set_bitrate(self.bitrate);
+        encoder
+    }
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("."),
+            codec: Codec::Mp3,
+            naming_scheme: "%n - %t".to_string(),
+            bitrate: 64,
+            title: None,
+            author: None,
+            narrator: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartOptions {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) max_duration: Option<u32>,
+    pub(crate) max_size: Option<u64>,
+    pub(crate) naming_scheme: String,
+}
+
+impl PartOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    pub fn max_duration(mut self, minutes: u32) -> Self {
+        self.max_duration = Some(minutes);
+        self
+    }
+
+    pub fn max_size(mut self, megabytes: u64) -> Self {
+        self.max_size = Some(megabytes);
+        self
+    }
+
+    pub fn naming_scheme(mut self, naming_scheme: impl Into<String>) -> Self {
+        self.naming_scheme = naming_scheme.into();
+        self
+    }
+}
+
+impl Default for PartOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("."),
+            max_duration: None,
+            max_size: None,
+            naming_scheme: "Part %n".to_string(),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    backend::{JobInput, MediaBackend, TranscodeJob},
+    encoder::EncoderSettings,
+    helper::ffmpeg_timestamp,
+    loudness::{LoudnessMeasurement, LoudnessTarget},
+    progress::Event,
+    Error, Result,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceSettings {
+    threshold: f64,
+    min_duration: u32,
+}
+
+impl SilenceSettings {
+    pub fn new(threshold: f64, min_duration: u32) -> Self {
+        Self {
+            threshold,
+            min_duration,
+        }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn min_duration(&self) -> u32 {
+        self.min_duration
+    }
+
+    pub(crate) fn detection_filter(&self) -> String {
+        format!(
+            "silencedetect=noise={}dB:d={}",
+            self.threshold,
+            ffmpeg_timestamp(self.min_duration)
+        )
+    }
+}
+
+impl Default for SilenceSettings {
+    fn default() -> Self {
+        SilenceSettings::new(-50.0, 500)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFile {
+    path: PathBuf,
+    duration: u32,
+    trim_start: u32,
+    trim_end: u32,
+    gap: u32,
+    loudness_filter: Option<String>,
+}
+
+impl InputFile {
+    pub fn new(path: impl Into<PathBuf>, backend: &dyn MediaBackend) -> Result<Self> {
+        let path = path.into();
+        let duration = backend.duration(&path)?;
+        Ok(Self {
+            path,
+            duration,
+            trim_start: 0,
+            trim_end: duration,
+            gap: 0,
+            loudness_filter: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn duration(&self) -> u32 {
+        self.trim_end - self.trim_start + self.gap
+    }
+
+    pub fn is_processed(&self) -> bool {
+        self.is_trimmed() || self.gap > 0 || self.loudness_filter.is_some()
+    }
+
+    fn is_trimmed(&self) -> bool {
+        self.trim_start > 0 || self.trim_end < self.duration
+    }
+
+    pub fn set_gap(&mut self, gap: u32) {
+        self.gap = gap;
+    }
+
+    pub fn trim_silence(
+        &mut self,
+        settings: &SilenceSettings,
+        backend: &dyn MediaBackend,
+    ) -> Result<()> {
+        let silences = backend.detect_silence(&self.path, settings)?;
+
+        let tolerance = 10;
+        if let Some((start, Some(end))) = silences.first() {
+            if *start <= tolerance {
+                self.trim_start = (*end).min(self.duration);
+            }
+        }
+        if let Some((start, end)) = silences.last() {
+            let reaches_end = end.is_none_or(|end| end + tolerance >= self.duration);
+            if reaches_end && *start > self.trim_start {
+                self.trim_end = (*start).min(self.duration);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn normalize_loudness(
+        &mut self,
+        target: &LoudnessTarget,
+        backend: &dyn MediaBackend,
+    ) -> Result<()> {
+        let measurement = LoudnessMeasurement::measure(&self.path, target, backend)?;
+        self.loudness_filter = Some(target.normalization_filter(&measurement));
+        Ok(())
+    }
+
+    pub fn filter_chain(&self, sample_rate: u32) -> String {
+        let mut filters: Vec<String> = Vec::new();
+        if self.is_trimmed() {
+            filters.push(format!(
+                "atrim=start={}:end={},asetpts=PTS-STARTPTS",
+                ffmpeg_timestamp(self.trim_start),
+                ffmpeg_timestamp(self.trim_end)
+            ));
+        }
+        if let Some(loudness_filter) = &self.loudness_filter {
+            filters.push(loudness_filter.clone());
+        }
+        filters.push(format!("aresample={sample_rate}"));
+        if self.gap > 0 {
+            filters.push(format!("apad=pad_dur={}", ffmpeg_timestamp(self.gap)));
+        }
+        filters.join(",")
+    }
+}
+
+#[derive(Clone)]
+pub struct SegmentEncoder<'a> {
+    encoder: EncoderSettings,
+    sample_rate: u32,
+    backend: &'a dyn MediaBackend,
+}
+
+impl<'a> SegmentEncoder<'a> {
+    pub fn new(encoder: EncoderSettings, sample_rate: u32, backend: &'a dyn MediaBackend) -> Self {
+        Self {
+            encoder,
+            sample_rate,
+            backend,
+        }
+    }
+
+    pub fn encode(&self, input: &InputFile, output: &Path) -> Result<()> {
+        let input = JobInput::filtered(input.path(), input.filter_chain(self.sample_rate));
+        let job = TranscodeJob::new(vec![input], self.encoder.clone(), output);
+        self.backend.transcode(&job)
+    }
+
+    pub fn segment_path(&self, input: &InputFile, work_dir: &Path) -> Result<PathBuf> {
+        let mut hasher = Sha256::new();
+        File::open(input.path())
+            .and_then(|mut file| io::copy(&mut file, &mut hasher))
+            .map_err(|source| Error::FileError {
+                path: input.path().to_path_buf(),
+                source,
+            })?;
+        hasher.update(self.encoder.ffmpeg_arguments()?.join(" "));
+        hasher.update(input.filter_chain(self.sample_rate));
+        Ok(work_dir.join(format!(
+            "{:x}.{}",
+            hasher.finalize(),
+            self.encoder.codec().extension()
+        )))
+    }
+
+    pub fn encode_cached(&self, input: &InputFile, work_dir: &Path) -> Result<(PathBuf, bool)> {
+        let segment = self.segment_path(input, work_dir)?;
+        if segment.exists() {
+            return Ok((segment, true));
+        }
+        let partial =
+            segment.with_extension(format!("partial.{}", self.encoder.codec().extension()));
+        match fs::remove_file(&partial) {
+            Err(source) if source.kind() != io::ErrorKind::NotFound => {
+                return Err(Error::FileError {
+                    path: partial,
+                    source,
+                })
+            }
+            _ => {}
+        }
+        self.encode(input, &partial)?;
+        fs::rename(&partial, &segment).map_err(|source| Error::FileError {
+            path: segment.clone(),
+            source,
+        })?;
+        Ok((segment, false))
+    }
+
+    pub fn encode_all(
+        &self,
+        inputs: &[InputFile],
+        work_dir: &Path,
+        jobs: usize,
+    ) -> Result<Vec<PathBuf>> {
+        let work_dir_error = |source| Error::FileError {
+            path: work_dir.to_path_buf(),
+            source,
+        };
+        fs::create_dir_all(work_dir).map_err(work_dir_error)?;
+        let work_dir = &work_dir.canonicalize().map_err(work_dir_error)?;
+        let segments: Mutex<Vec<PathBuf>> = Mutex::new(vec![PathBuf::new(); inputs.len()]);
+        let next = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let first_error: Mutex<Option<Error>> = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= inputs.len() || failed.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    match self.encode_cached(&inputs[i], work_dir) {
+                        Ok((segment, reused)) => {
+                            let count = finished.fetch_add(1, Ordering::SeqCst) + 1;
+                            self.backend.report(Event::Status(format!(
+                                "{} {count}/{}: {}",
+                                if reused { "Reused" } else { "Encoded" },
+                                inputs.len(),
+                                inputs[i].path().to_string_lossy()
+                            )));
+                            if let Ok(mut segments) = segments.lock() {
+                                segments[i] = segment;
+                            }
+                        }
+                        Err(err) => {
+                            failed.store(true, Ordering::SeqCst);
+                            if let Ok(mut first_error) = first_error.lock() {
+                                first_error.get_or_insert(err);
+                            }
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(err) = first_error.into_inner().ok().flatten() {
+            return Err(err);
+        }
+        Ok(segments.into_inner().unwrap_or_default())
+    }
+}
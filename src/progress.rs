@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+const BAR_WIDTH: usize = 30;
+
+/// Something a long running command wants to tell the user about. Progress
+/// events are only sent while ffmpeg encodes a job with a known duration and
+/// are followed by a single `ProgressEnd`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Status(String),
+    Progress {
+        position: u32,
+        total: u32,
+        speed: Option<f64>,
+    },
+    ProgressEnd,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgressBar {
+    total: Option<u32>,
+    started: Instant,
+}
+
+impl ProgressBar {
+    pub fn new(total: Option<u32>) -> Self {
+        Self {
+            total: total.filter(|total| *total > 0),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn line(&self, position: u32, speed: Option<f64>) -> String {
+        let speed = speed.map_or("".to_string(), |speed| format!("  {speed:.1}x"));
+        match self.total {
+            Some(total) => {
+                let fraction = (position as f64 / total as f64).clamp(0.0, 1.0);
+                let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+                format!(
+                    "[{}{}] {:5.1}%  {} / {}  ETA {}{speed}",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    fraction * 100.0,
+                    format_duration(position),
+                    format_duration(total),
+                    self.eta(position, total)
+                        .map_or("--:--:--".to_string(), format_duration),
+                )
+            }
+            None => format!("{}{speed}", format_duration(position)),
+        }
+    }
+
+    fn eta(&self, position: u32, total: u32) -> Option<u32> {
+        let elapsed = self.started.elapsed().as_millis() as f64;
+        if position == 0 || elapsed == 0.0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(position) as f64;
+        Some((remaining * elapsed / position as f64) as u32)
+    }
+}
+
+pub fn format_duration(milliseconds: u32) -> String {
+    let seconds = milliseconds / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,319 @@
+use crate::{
+    chapters::ChapterList,
+    encoder::{Codec, EncodingPath},
+    loudness::{LoudnessMeasurement, LoudnessTarget},
+    metadata::BookMetadata,
+    tools::ToolLocator,
+    Error, Result,
+};
+use prettytable::{row, Table};
+use std::{fmt::Display, path::PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTags {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub composer: Option<String>,
+    pub disc: Option<u32>,
+    pub track: Option<u32>,
+    pub metadata: BookMetadata,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagReport {
+    pub files: Vec<FileTags>,
+}
+
+impl Display for TagReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = Table::new();
+        table.set_titles(row![
+            b->"File",
+            b->"Title",
+            b->"Album",
+            b->"Author\n(Artist)",
+            b->"Album Artist",
+            b->"Narrator\n(Composer)",
+            b->"Disc",
+            b->"Track",
+            b->"Series",
+            b->"Year",
+        ]);
+        for file in &self.files {
+            let metadata = &file.metadata;
+            let series = match (metadata.series(), metadata.series_index()) {
+                (Some(series), Some(index)) => format!("{series} #{index}"),
+                (Some(series), None) => series,
+                (None, Some(index)) => format!("#{index}"),
+                (None, None) => String::new(),
+            };
+            table.add_row(row![
+                file_name(&file.path),
+                file.title.as_deref().unwrap_or_default(),
+                file.album.as_deref().unwrap_or_default(),
+                file.artist.as_deref().unwrap_or_default(),
+                file.album_artist.as_deref().unwrap_or_default(),
+                file.composer.as_deref().unwrap_or_default(),
+                file.disc.map(|disc| disc.to_string()).unwrap_or_default(),
+                file.track
+                    .map(|track| track.to_string())
+                    .unwrap_or_default(),
+                series,
+                metadata
+                    .year()
+                    .map(|year| year.to_string())
+                    .unwrap_or_default(),
+            ]);
+        }
+        write!(f, "{}", table)?;
+
+        let details: Vec<&FileTags> = self
+            .files
+            .iter()
+            .filter(|file| {
+                let metadata = &file.metadata;
+                metadata.publisher().is_some()
+                    || metadata.language().is_some()
+                    || metadata.isbn().is_some()
+                    || metadata.asin().is_some()
+                    || metadata.description().is_some()
+            })
+            .collect();
+        if details.is_empty() {
+            return Ok(());
+        }
+        let mut table = Table::new();
+        table.set_titles(row![
+            b->"File",
+            b->"Publisher",
+            b->"Language",
+            b->"ISBN",
+            b->"ASIN",
+            b->"Description",
+        ]);
+        for file in details {
+            let metadata = &file.metadata;
+            table.add_row(row![
+                file_name(&file.path),
+                metadata.publisher().unwrap_or_default(),
+                metadata.language().unwrap_or_default(),
+                metadata.isbn().unwrap_or_default(),
+                metadata.asin().unwrap_or_default(),
+                metadata.description().unwrap_or_default(),
+            ]);
+        }
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug)]
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub error: Option<Error>,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub files: Vec<BatchResult>,
+}
+
+impl BatchReport {
+    pub fn failed(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.error.is_some())
+            .count()
+    }
+
+    pub fn check(&self) -> Result<()> {
+        match self.failed() {
+            0 => Ok(()),
+            failed => Err(Error::BatchError(failed, self.files.len())),
+        }
+    }
+}
+
+impl Display for BatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = Table::new();
+        table.set_titles(row![b->"File", b->"Result"]);
+        for file in &self.files {
+            match &file.error {
+                Some(err) => table.add_row(row![file_name(&file.path), Fr->err.to_string()]),
+                None => table.add_row(row![file_name(&file.path), Fg->"ok"]),
+            };
+        }
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenamePlan {
+    pub moves: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Display for RenamePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = Table::new();
+        table.set_titles(row![b->"From", b->"To"]);
+        for (from, to) in &self.moves {
+            table.add_row(row![from.to_string_lossy(), to.to_string_lossy()]);
+        }
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoudnessReport {
+    pub target: LoudnessTarget,
+    pub measurements: Vec<(PathBuf, LoudnessMeasurement)>,
+}
+
+impl LoudnessReport {
+    pub fn mean(&self) -> f64 {
+        self.measurements
+            .iter()
+            .map(|(_, measurement)| measurement.integrated)
+            .sum::<f64>()
+            / self.measurements.len().max(1) as f64
+    }
+}
+
+impl Display for LoudnessReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mean = self.mean();
+        let mut table = Table::new();
+        table.set_titles(row![
+            b->"File",
+            b->"Integrated\n(LUFS)",
+            b->"True peak\n(dBTP)",
+            b->"Range\n(LU)",
+            b->"From mean\n(LU)",
+            b->"From target\n(LU)",
+        ]);
+        for (path, measurement) in &self.measurements {
+            table.add_row(row![
+                file_name(path),
+                r->format!("{:.1}", measurement.integrated),
+                r->format!("{:.1}", measurement.true_peak),
+                r->format!("{:.1}", measurement.loudness_range),
+                r->format!("{:+.1}", measurement.integrated - mean),
+                r->format!("{:+.1}", measurement.integrated - self.target.integrated()),
+            ]);
+        }
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CombineReport {
+    pub output: PathBuf,
+    pub chapters: ChapterList,
+    pub encoding: EncodingPath,
+    pub conflicts: Vec<TagConflict>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagConflict {
+    pub field: &'static str,
+    pub values: Vec<(String, usize)>,
+}
+
+impl Display for TagConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|(value, count)| match count {
+                1 => format!("'{value}' (1 file)"),
+                count => format!("'{value}' ({count} files)"),
+            })
+            .collect();
+        write!(
+            f,
+            "the input files disagree on the {}: {}, using '{}'",
+            self.field,
+            values.join(", "),
+            self.values
+                .first()
+                .map(|(value, _)| value.as_str())
+                .unwrap_or_default()
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitPart {
+    pub path: PathBuf,
+    pub chapters: ChapterList,
+    pub exceeds_limit: bool,
+}
+
+#[derive(Debug)]
+pub struct DoctorReport {
+    pub config_path: Option<PathBuf>,
+    pub locator: ToolLocator,
+    pub ffmpeg_version: Option<String>,
+    pub ffprobe_version: Option<String>,
+    pub encoders: Vec<(Codec, bool)>,
+    pub problem: Option<Error>,
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.config_path {
+            Some(path) if path.exists() => writeln!(f, "Config file: {}", path.to_string_lossy())?,
+            Some(path) => writeln!(f, "Config file: {} (not found)", path.to_string_lossy())?,
+            None => writeln!(f, "Config file: none")?,
+        }
+
+        let mut table = Table::new();
+        table.set_titles(row![b->"Tool", b->"Path", b->"Found via", b->"Version"]);
+        for (tool, path, source, version) in [
+            (
+                "ffmpeg",
+                self.locator.ffmpeg_path(),
+                self.locator.ffmpeg_source(),
+                &self.ffmpeg_version,
+            ),
+            (
+                "ffprobe",
+                self.locator.ffprobe_path(),
+                self.locator.ffprobe_source(),
+                &self.ffprobe_version,
+            ),
+        ] {
+            let version = version.as_deref().unwrap_or("not found");
+            table.add_row(row![tool, path, source, version]);
+        }
+        write!(f, "{}", table)?;
+
+        let mut table = Table::new();
+        table.set_titles(row![b->"Codec", b->"Encoder", b->"Available"]);
+        for (codec, available) in &self.encoders {
+            table.add_row(row![
+                codec.codec_name().unwrap_or_default(),
+                codec.encoder(),
+                if *available { "yes" } else { "no" }
+            ]);
+        }
+        write!(f, "{}", table)?;
+
+        if self.problem.is_none() {
+            writeln!(
+                f,
+                "Everything needed to combine and split audiobooks was found"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
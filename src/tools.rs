@@ -0,0 +1,207 @@
+use crate::{backend::FfmpegBackend, Error, Result};
+use serde::Deserialize;
+use std::{
+    env,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub const MIN_FFMPEG_MAJOR_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ToolConfig {
+    ffmpeg: Option<String>,
+    ffprobe: Option<String>,
+}
+
+impl ToolConfig {
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("AUDIOBOOK_TAGGER_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("audiobook-tagger").join("config.toml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let Some(path) = ToolConfig::path() else {
+            return Ok(ToolConfig::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(toml) => Ok(toml::from_str(&toml)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(ToolConfig::default()),
+            Err(source) => Err(Error::FileError { path, source }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolSource {
+    CommandLine,
+    Environment,
+    ConfigFile,
+    DerivedFromFfmpeg,
+    SearchPath,
+}
+
+impl Display for ToolSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self {
+            ToolSource::CommandLine => "command line",
+            ToolSource::Environment => "environment variable",
+            ToolSource::ConfigFile => "config file",
+            ToolSource::DerivedFromFfmpeg => "next to ffmpeg",
+            ToolSource::SearchPath => "PATH",
+        };
+        write!(f, "{}", source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolLocator {
+    ffmpeg_path: String,
+    ffmpeg_source: ToolSource,
+    ffprobe_path: String,
+    ffprobe_source: ToolSource,
+}
+
+impl ToolLocator {
+    pub fn locate(ffmpeg_override: Option<&str>) -> Result<Self> {
+        let config = ToolConfig::load()?;
+
+        let (ffmpeg_path, ffmpeg_source) = if let Some(path) = ffmpeg_override {
+            (path.to_string(), ToolSource::CommandLine)
+        } else if let Ok(path) = env::var("FFMPEG") {
+            (path, ToolSource::Environment)
+        } else if let Some(path) = config.ffmpeg {
+            (path, ToolSource::ConfigFile)
+        } else {
+            ("ffmpeg".to_string(), ToolSource::SearchPath)
+        };
+
+        let derived_ffprobe_path = derive_ffprobe_path(&ffmpeg_path);
+        let (ffprobe_path, ffprobe_source) = if let (ToolSource::CommandLine, Some(path)) =
+            (ffmpeg_source, derived_ffprobe_path.clone())
+        {
+            (path, ToolSource::DerivedFromFfmpeg)
+        } else if let Ok(path) = env::var("FFPROBE") {
+            (path, ToolSource::Environment)
+        } else if let Some(path) = config.ffprobe {
+            (path, ToolSource::ConfigFile)
+        } else if let Some(path) = derived_ffprobe_path {
+            (path, ToolSource::DerivedFromFfmpeg)
+        } else {
+            ("ffprobe".to_string(), ToolSource::SearchPath)
+        };
+
+        Ok(Self {
+            ffmpeg_path,
+            ffmpeg_source,
+            ffprobe_path,
+            ffprobe_source,
+        })
+    }
+
+    pub fn ffmpeg_path(&self) -> &str {
+        &self.ffmpeg_path
+    }
+
+    pub fn ffmpeg_source(&self) -> ToolSource {
+        self.ffmpeg_source
+    }
+
+    pub fn ffprobe_path(&self) -> &str {
+        &self.ffprobe_path
+    }
+
+    pub fn ffprobe_source(&self) -> ToolSource {
+        self.ffprobe_source
+    }
+
+    pub fn backend(&self) -> FfmpegBackend {
+        let mut backend = FfmpegBackend::new(&self.ffmpeg_path);
+        backend.set_ffprobe_path(&self.ffprobe_path);
+        backend
+    }
+
+    pub fn check_ffmpeg(&self, required_encoders: &[&str]) -> Result<()> {
+        let version = tool_version(&self.ffmpeg_path)
+            .map_err(|_| Error::FfmpegNotFoundError(self.ffmpeg_path.clone()))?;
+        if major_version(&version).is_some_and(|major| major < MIN_FFMPEG_MAJOR_VERSION) {
+            return Err(Error::UnsupportedToolVersionError(
+                self.ffmpeg_path.clone(),
+                version,
+            ));
+        }
+        let encoders = available_encoders(&self.ffmpeg_path)?;
+        for encoder in required_encoders {
+            if !encoders.iter().any(|available| available == encoder) {
+                return Err(Error::MissingEncoderError(encoder.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_ffprobe(&self) -> Result<()> {
+        tool_version(&self.ffprobe_path).map_err(|_| Error::FfprobeNotFoundError())?;
+        Ok(())
+    }
+}
+
+fn derive_ffprobe_path(ffmpeg_path: &str) -> Option<String> {
+    let ffmpeg_path = Path::new(ffmpeg_path);
+    let parent = ffmpeg_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())?;
+    let file_name = ffmpeg_path.file_name()?.to_string_lossy();
+    let ffprobe_name = file_name.replacen("ffmpeg", "ffprobe", 1);
+    if ffprobe_name == file_name {
+        return None;
+    }
+    Some(parent.join(ffprobe_name).to_string_lossy().into_owned())
+}
+
+pub fn tool_version(tool_path: &str) -> Result<String> {
+    let output = Command::new(tool_path).arg("-version").output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first_line = stdout.lines().next().unwrap_or_default();
+    Ok(first_line
+        .split_whitespace()
+        .nth(2)
+        .unwrap_or("unknown")
+        .to_string())
+}
+
+fn major_version(version: &str) -> Option<u32> {
+    version
+        .trim_start_matches('n')
+        .split(['.', '-'])
+        .next()?
+        .parse()
+        .ok()
+}
+
+pub fn available_encoders(ffmpeg_path: &str) -> Result<Vec<String>> {
+    let output = match Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::FfmpegNotFoundError(ffmpeg_path.to_string()))
+        }
+        Err(err) => return Err(Error::IoError(err)),
+    };
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect())
+}
@@ -0,0 +1,241 @@
+use audiobook_tagger::{
+    backend::{AudioInfo, JobInput, MediaBackend, MediaInfo, TranscodeJob},
+    chapters::Chapter,
+    combine_files,
+    encoder::{Codec, EncodingPath},
+    loudness::{LoudnessMeasurement, LoudnessTarget},
+    processing::SilenceSettings,
+    split_file, CombineOptions, Error, Result, SplitOptions,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tempfile::TempDir;
+
+#[derive(Default)]
+struct FakeBackend {
+    durations: BTreeMap<PathBuf, u32>,
+    codecs: BTreeMap<PathBuf, String>,
+    chapters: Vec<Chapter>,
+    jobs: Mutex<Vec<TranscodeJob>>,
+}
+
+impl FakeBackend {
+    fn jobs(&self) -> Vec<TranscodeJob> {
+        self.jobs.lock().unwrap().clone()
+    }
+}
+
+impl MediaBackend for FakeBackend {
+    fn probe(&self, path: &Path) -> Result<MediaInfo> {
+        Ok(MediaInfo {
+            path: path.to_path_buf(),
+            audio: Some(AudioInfo {
+                codec: self.codecs.get(path).cloned().unwrap_or("aac".to_string()),
+                sample_rate: 44100,
+                channels: 2,
+                channel_layout: "stereo".to_string(),
+                duration: self.durations.get(path).copied().unwrap_or_default(),
+            }),
+            tags: BTreeMap::from([
+                ("title".to_string(), "The Book".to_string()),
+                ("artist".to_string(), "The Author".to_string()),
+            ]),
+            chapters: self.chapters.clone(),
+        })
+    }
+
+    fn transcode(&self, job: &TranscodeJob) -> Result<()> {
+        self.jobs.lock().unwrap().push(job.clone());
+        Ok(())
+    }
+
+    fn detect_silence(
+        &self,
+        _path: &Path,
+        _settings: &SilenceSettings,
+    ) -> Result<Vec<(u32, Option<u32>)>> {
+        Ok(Vec::new())
+    }
+
+    fn measure_loudness(
+        &self,
+        _path: &Path,
+        _target: &LoudnessTarget,
+    ) -> Result<LoudnessMeasurement> {
+        Ok(LoudnessMeasurement {
+            integrated: -18.0,
+            true_peak: -1.5,
+            loudness_range: 5.0,
+            threshold: -28.0,
+            target_offset: 0.0,
+        })
+    }
+}
+
+fn audio_files(
+    dir: &Path,
+    extension: &str,
+    durations: &[u32],
+) -> (Vec<PathBuf>, BTreeMap<PathBuf, u32>) {
+    let mut paths = Vec::new();
+    let mut by_path = BTreeMap::new();
+    for (i, duration) in durations.iter().enumerate() {
+        let path = dir.join(format!("{i}.{extension}"));
+        fs::write(&path, b"").unwrap();
+        by_path.insert(path.clone(), *duration);
+        paths.push(path);
+    }
+    (paths, by_path)
+}
+
+#[test]
+fn combine_stream_copies_matching_inputs() {
+    let dir = TempDir::new().unwrap();
+    let (paths, durations) = audio_files(dir.path(), "m4a", &[60_000, 90_000]);
+    let backend = FakeBackend {
+        durations,
+        ..FakeBackend::default()
+    };
+    let output = dir.path().join("book.m4b");
+    let options = CombineOptions::new().title("Title").author("Author");
+
+    let report = combine_files(&paths, &output, &options, &backend).unwrap();
+
+    assert!(matches!(report.encoding, EncodingPath::StreamCopy(_)));
+    let jobs = backend.jobs();
+    assert_eq!(jobs.len(), 1);
+    let job = &jobs[0];
+    assert_eq!(
+        job.inputs,
+        paths.iter().map(JobInput::new).collect::<Vec<_>>()
+    );
+    assert_eq!(job.encoder.codec(), Codec::Copy);
+    assert_eq!(job.output, output);
+    assert_eq!(job.duration, Some(150_000));
+    let chapters = job.chapters.as_ref().unwrap();
+    assert_eq!(chapters.title(), "Title");
+    assert_eq!(chapters.author(), "Author");
+    let ranges: Vec<(u32, u32)> = chapters
+        .iter()
+        .map(|chapter| (chapter.start(), chapter.end()))
+        .collect();
+    assert_eq!(ranges, [(0, 60_000), (60_000, 150_000)]);
+}
+
+#[test]
+fn combine_takes_mp3_durations_from_the_backend() {
+    let dir = TempDir::new().unwrap();
+    let (paths, durations) = audio_files(dir.path(), "mp3", &[30_000, 45_000]);
+    let backend = FakeBackend {
+        codecs: paths
+            .iter()
+            .map(|path| (path.clone(), "mp3".to_string()))
+            .collect(),
+        durations,
+        ..FakeBackend::default()
+    };
+    let output = dir.path().join("book.m4b");
+
+    let report = combine_files(&paths, &output, &CombineOptions::new(), &backend).unwrap();
+
+    assert!(matches!(report.encoding, EncodingPath::Reencode(_)));
+    let jobs = backend.jobs();
+    assert_eq!(jobs[0].duration, Some(75_000));
+    let ranges: Vec<(u32, u32)> = jobs[0]
+        .chapters
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|chapter| (chapter.start(), chapter.end()))
+        .collect();
+    assert_eq!(ranges, [(0, 30_000), (30_000, 75_000)]);
+}
+
+#[test]
+fn combine_reencodes_when_bitrate_is_explicit() {
+    let dir = TempDir::new().unwrap();
+    let (paths, durations) = audio_files(dir.path(), "m4a", &[60_000, 90_000]);
+    let backend = FakeBackend {
+        durations,
+        ..FakeBackend::default()
+    };
+    let output = dir.path().join("book.m4b");
+    let options = CombineOptions::new().bitrate(96);
+
+    let report = combine_files(&paths, &output, &options, &backend).unwrap();
+
+    assert!(matches!(report.encoding, EncodingPath::Reencode(_)));
+    assert_eq!(backend.jobs()[0].encoder.codec(), Codec::Aac);
+}
+
+#[test]
+fn combine_copy_codec_requires_uniform_aac_inputs() {
+    let dir = TempDir::new().unwrap();
+    let (paths, durations) = audio_files(dir.path(), "m4a", &[60_000, 90_000]);
+    let output = dir.path().join("book.m4b");
+    let options = CombineOptions::new().codec(Codec::Copy);
+
+    let backend = FakeBackend {
+        durations: durations.clone(),
+        ..FakeBackend::default()
+    };
+    let report = combine_files(&paths, &output, &options, &backend).unwrap();
+    assert!(matches!(report.encoding, EncodingPath::StreamCopy(_)));
+
+    let backend = FakeBackend {
+        durations,
+        codecs: BTreeMap::from([(paths[1].clone(), "mp3".to_string())]),
+        ..FakeBackend::default()
+    };
+    let result = combine_files(&paths, &output, &options, &backend);
+    assert!(matches!(result, Err(Error::EncoderSettingsError(_))));
+    assert!(backend.jobs().is_empty());
+}
+
+#[test]
+fn split_cuts_one_job_per_chapter() {
+    let dir = TempDir::new().unwrap();
+    let (paths, durations) = audio_files(dir.path(), "m4a", &[90_000]);
+    let backend = FakeBackend {
+        durations,
+        chapters: vec![
+            Chapter::new("Intro", 0, 50_000),
+            Chapter::new("End", 50_000, 90_000),
+        ],
+        ..FakeBackend::default()
+    };
+    let output_dir = dir.path().join("chapters");
+    let options = SplitOptions::new()
+        .output_dir(&output_dir)
+        .codec(Codec::Aac)
+        .naming_scheme("%n - %t");
+
+    let outputs = split_file(&paths[0], &options, &backend).unwrap();
+
+    assert_eq!(
+        outputs,
+        [
+            output_dir.join("1 - Intro.m4a"),
+            output_dir.join("2 - End.m4a")
+        ]
+    );
+    let jobs = backend.jobs();
+    let ranges: Vec<Option<(u32, u32)>> = jobs.iter().map(|job| job.range).collect();
+    assert_eq!(ranges, [Some((0, 50_000)), Some((50_000, 90_000))]);
+    for (job, output) in jobs.iter().zip(&outputs) {
+        assert_eq!(job.inputs, [JobInput::new(&paths[0])]);
+        assert_eq!(job.encoder.codec(), Codec::Aac);
+        assert_eq!(&job.output, output);
+        assert!(job.chapters.is_none());
+    }
+    assert!(jobs[1]
+        .metadata
+        .contains(&("track".to_string(), "2/2".to_string())));
+    assert!(jobs[0]
+        .metadata
+        .contains(&("album".to_string(), "The Book".to_string())));
+}